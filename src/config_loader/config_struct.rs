@@ -1,18 +1,101 @@
 use std::fmt;
+use std::net::IpAddr;
 use serde::{de, Deserialize, Deserializer};
 use serde::de::{MapAccess, Visitor};
 
+/// InfluxDB 2.x is used when `bucket` is given, with `org` and `token`.
+/// Otherwise InfluxDB 1.x is used with `database`, and optionally `username` and `password`.
 #[derive(Debug, Deserialize)]
 pub struct InfluxConfig {
     pub url: String,
-    pub database: String,
+    /// 1.x only
+    pub database: Option<String>,
+    /// 1.x only
     pub username: Option<String>,
+    /// 1.x only
     pub password: Option<String>,
+    /// 2.x only
+    pub org: Option<String>,
+    /// 2.x only
+    pub bucket: Option<String>,
+    /// 2.x only, an API token with write permission to the bucket
+    pub token: Option<String>,
+    #[serde(default)]
+    pub precision: InfluxPrecision,
+}
+
+/// The precision of the timestamps written to InfluxDB.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum InfluxPrecision {
+    #[default]
+    #[serde(rename = "s")]
+    Seconds,
+    #[serde(rename = "ms")]
+    Milliseconds,
+    #[serde(rename = "us")]
+    Microseconds,
+    #[serde(rename = "ns")]
+    Nanoseconds,
+}
+
+/// A sink of download events, selected by `type`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum SinkConfig {
+    #[serde(rename = "influx")]
+    Influx(InfluxConfig),
+    #[serde(rename = "jsonl")]
+    Jsonl(JsonlSinkConfig),
+    #[serde(rename = "sqlite")]
+    Sqlite(SqliteSinkConfig),
+}
+
+/// When the JSON lines file is rotated.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum RotatePolicy {
+    /// On the first write of each day, in UTC.
+    #[default]
+    #[serde(rename = "daily")]
+    Daily,
+    /// When the file would exceed `max_size`.
+    #[serde(rename = "size")]
+    Size,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JsonlSinkConfig {
+    /// The current file, rotated ones are named like `{path}.20240101T000000`.
+    pub path: String,
+    #[serde(default)]
+    pub rotate: RotatePolicy,
+    /// in bytes, only for `size` rotation, default to 100 MiB
+    #[serde(default = "default_jsonl_max_size")]
+    pub max_size: u64,
+    /// How many rotated files are kept, the oldest ones are deleted.
+    #[serde(default = "default_jsonl_retention")]
+    pub retention: usize,
+}
+
+fn default_jsonl_max_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_jsonl_retention() -> usize {
+    30
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SqliteSinkConfig {
+    /// The database file, created if it does not exist.
+    pub path: String,
+    /// Downloads older than this are deleted, kept forever when not provided.
+    pub retention_days: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct OnedriveConfig {
-    /// Always "onedrive"
+    /// Always "onedrive", only checked when the config is deserialized.
+    #[allow(unused)]
     pub drive_type: String,
 
     /// The refresh token for the onedrive account.
@@ -26,6 +109,57 @@ pub struct OnedriveConfig {
     /// The client secret for the application.
     /// You can get it from the Azure portal with the client id.
     pub client_secret: String,
+
+    /// The tenant id (or domain) of the organization, default to "common".
+    /// OneDrive for Business accounts which are not multi-tenant apps need it.
+    pub tenant: Option<String>,
+
+    /// The drive to use, as a graph api path, default to "/me/drive".
+    /// - SharePoint document library: "/sites/{site-id}/drive"
+    /// - a drive by id: "/drives/{drive-id}"
+    pub drive: Option<String>,
+
+    /// The national cloud of the account, default to global.
+    pub cloud: Option<OnedriveCloud>,
+
+    /// Override the base url of the login endpoint (like "https://login.microsoftonline.com"), mostly for testing.
+    pub auth_url: Option<String>,
+
+    /// Override the base url of the graph api (like "https://graph.microsoft.com"), mostly for testing.
+    pub graph_url: Option<String>,
+
+    /// The name of the drive, default to `drive{index}` where `index` is its position in `drives`.
+    /// It is used as the suffix when files are renamed for conflicts.
+    pub name: Option<String>,
+
+    /// Files on drives with larger priority are preferred when they conflict, and downloaded more by the `weighted` strategy, default to 0.
+    pub priority: Option<i64>,
+
+    /// A small file (like "/canary.txt") downloaded by `HEAD` to check the health of the drive.
+    /// When not provided, only the drive itself is requested.
+    pub canary: Option<String>,
+
+    /// Gitignore-style patterns (relative to the root of the drive) of the entries removed from the tree.
+    pub hide: Option<Vec<String>>,
+
+    /// Gitignore-style patterns (relative to the root of the drive) of the entries left out of listings,
+    /// which can still be downloaded by their exact paths.
+    pub unlisted: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum OnedriveCloud {
+    #[serde(rename = "global")]
+    Global,
+    /// Operated by 21Vianet
+    #[serde(rename = "china")]
+    China,
+    /// US Government L4
+    #[serde(rename = "us_gov")]
+    UsGov,
+    /// US Government L5 (DOD)
+    #[serde(rename = "us_gov_dod")]
+    UsGovDod,
 }
 
 #[derive(Debug)]
@@ -38,6 +172,11 @@ impl<'de> Deserialize<'de> for DriveConfig {
         where
             D: Deserializer<'de>,
     {
+        const FIELDS: &[&str] = &[
+            "drive_type", "refresh_token", "client_id", "client_secret",
+            "tenant", "drive", "cloud", "auth_url", "graph_url", "name", "priority", "canary",
+            "hide", "unlisted",
+        ];
         struct DriveConfigVisitor;
 
         impl<'de> Visitor<'de> for DriveConfigVisitor {
@@ -55,14 +194,35 @@ impl<'de> Deserialize<'de> for DriveConfig {
                 let mut refresh_token = None;
                 let mut client_id = None;
                 let mut client_secret = None;
+                let mut tenant = None;
+                let mut drive = None;
+                let mut cloud = None;
+                let mut auth_url = None;
+                let mut graph_url = None;
+                let mut name = None;
+                let mut priority = None;
+                let mut canary = None;
+                let mut hide = None;
+                let mut unlisted = None;
 
-                while let Some(key) = map.next_key()? {
-                    match key {
+                // keys are owned, since `serde_json::from_reader` can not lend borrowed strings
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
                         "drive_type" => { drive_type = Some(map.next_value()?); },
                         "refresh_token" => { refresh_token = Some(map.next_value()?); },
                         "client_id" => { client_id = Some(map.next_value()?); },
                         "client_secret" => { client_secret = Some(map.next_value()?); },
-                        _ => { return Err(de::Error::unknown_field(key, &["drive_type", "refresh_token", "client_id", "client_secret"])); }
+                        "tenant" => { tenant = Some(map.next_value()?); },
+                        "drive" => { drive = Some(map.next_value()?); },
+                        "cloud" => { cloud = Some(map.next_value()?); },
+                        "auth_url" => { auth_url = Some(map.next_value()?); },
+                        "graph_url" => { graph_url = Some(map.next_value()?); },
+                        "name" => { name = Some(map.next_value()?); },
+                        "priority" => { priority = Some(map.next_value()?); },
+                        "canary" => { canary = Some(map.next_value()?); },
+                        "hide" => { hide = Some(map.next_value()?); },
+                        "unlisted" => { unlisted = Some(map.next_value()?); },
+                        _ => { return Err(de::Error::unknown_field(&key, FIELDS)); }
                     }
                 }
 
@@ -77,6 +237,16 @@ impl<'de> Deserialize<'de> for DriveConfig {
                         refresh_token,
                         client_id,
                         client_secret,
+                        tenant,
+                        drive,
+                        cloud,
+                        auth_url,
+                        graph_url,
+                        name,
+                        priority,
+                        canary,
+                        hide,
+                        unlisted,
                     }))
                 } else {
                     Err(de::Error::custom("drive_type not supported"))
@@ -84,7 +254,6 @@ impl<'de> Deserialize<'de> for DriveConfig {
             }
         }
 
-        const FIELDS: &'static [&'static str] = &["drive_type", "refresh_token", "client_id", "client_secret"];
         deserializer.deserialize_struct("DriveConfig", FIELDS, DriveConfigVisitor)
     }
 }
@@ -92,14 +261,412 @@ impl<'de> Deserialize<'de> for DriveConfig {
 #[derive(Debug, Deserialize)]
 pub struct CacheSetting {
     pub refresh_interval: u64,  // in seconds, default to 600 seconds
+    #[serde(default = "default_link_max_age")]
+    pub link_max_age: u64,      // in seconds, download urls older than it will be resolved again, default to 3000 seconds
+    #[serde(default = "default_change_history")]
+    pub change_history: usize,  // the changes of this many refreshes are kept for `/api/changes`, default to 100
+}
+
+pub fn default_link_max_age() -> u64 {
+    3000
+}
+
+pub fn default_change_history() -> usize {
+    100
+}
+
+/// How to resolve files with same name but different contents on different drives.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ConflictPolicy {
+    /// Keep all of them, rename the ones on lower priority drives with a suffix of the drive name.
+    #[default]
+    #[serde(rename = "rename")]
+    Rename,
+    /// Keep the newest one.
+    #[serde(rename = "newest")]
+    Newest,
+    /// Keep the one on the drive with the highest priority.
+    #[serde(rename = "priority")]
+    Priority,
+    /// Refuse to combine the drives, the last successful tree will be kept.
+    #[serde(rename = "error")]
+    Error,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct MergeSetting {
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+}
+
+/// How to select one of the mirrors of a file.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum SelectStrategy {
+    #[default]
+    #[serde(rename = "random")]
+    Random,
+    /// Random, weighted by the `priority` of the drives: a drive gets `priority + 1` shares, at least 1.
+    #[serde(rename = "weighted")]
+    Weighted,
+    #[serde(rename = "round_robin")]
+    RoundRobin,
+    #[serde(rename = "least_recently_used")]
+    LeastRecentlyUsed,
+    /// The same client always gets the same mirror, by the hash of its ip.
+    #[serde(rename = "sticky")]
+    Sticky,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MirrorRule {
+    /// A file, or a directory which applies to all the files in it, like "/releases".
+    pub path: String,
+    pub strategy: SelectStrategy,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MirrorSetting {
+    #[serde(default)]
+    pub strategy: SelectStrategy,
+    /// The most specific rule matching the path overrides `strategy`.
+    #[serde(default)]
+    pub rules: Vec<MirrorRule>,
+    #[serde(default = "default_unhealthy_cooldown")]
+    pub unhealthy_cooldown: u64,    // in seconds, failed drives are skipped until they recover, and probed again after it, default to 300 seconds
+}
+
+pub fn default_unhealthy_cooldown() -> u64 {
+    300
+}
+
+impl Default for MirrorSetting {
+    fn default() -> Self {
+        MirrorSetting {
+            strategy: SelectStrategy::default(),
+            rules: Vec::new(),
+            unhealthy_cooldown: default_unhealthy_cooldown(),
+        }
+    }
+}
+
+impl MirrorSetting {
+    /// The strategy for the file at `path`.
+    pub fn strategy_for(&self, path: &str) -> SelectStrategy {
+        self.rules.iter()
+            .filter(|rule| {
+                let prefix = rule.path.trim_end_matches('/');
+                path == prefix || path.starts_with(&format!("{}/", prefix))
+            })
+            .max_by_key(|rule| rule.path.trim_end_matches('/').len())
+            .map(|rule| rule.strategy)
+            .unwrap_or(self.strategy)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HealthSetting {
+    pub interval: u64,  // in seconds, default to 60 seconds
+    /// `/readyz` reports not ready when fewer enabled drives are healthy.
+    #[serde(default = "default_min_healthy_drives")]
+    pub min_healthy_drives: usize,
+}
+
+fn default_min_healthy_drives() -> usize {
+    1
+}
+
+impl Default for HealthSetting {
+    fn default() -> Self {
+        HealthSetting {
+            interval: 60,
+            min_healthy_drives: default_min_healthy_drives(),
+        }
+    }
+}
+
+/// Gitignore-style patterns applied to the combined tree, relative to its root.
+#[derive(Debug, Deserialize, Default)]
+pub struct VisibilitySetting {
+    /// Entries removed from the tree, as if they did not exist.
+    #[serde(default)]
+    pub hide: Vec<String>,
+    /// Entries left out of listings, which can still be downloaded by their exact paths.
+    #[serde(default)]
+    pub unlisted: Vec<String>,
+}
+
+/// A directory which can only be listed or downloaded after being unlocked with the password.
+#[derive(Debug, Deserialize)]
+pub struct ProtectRule {
+    /// A directory path like "/private", or a glob like "/*/private" (`*` does not match `/`, `**` does).
+    /// Everything in the matched directories is protected.
+    pub path: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProtectionSetting {
+    /// The key to sign the unlock cookies. When not provided, a random one is used, and all the cookies expire on restart.
+    pub secret: Option<String>,
+    #[serde(default = "default_unlock_max_age")]
+    pub unlock_max_age: u64,    // in seconds, how long a directory stays unlocked, default to 1 day
+    pub rules: Vec<ProtectRule>,
+}
+
+pub fn default_unlock_max_age() -> u64 {
+    24 * 60 * 60
+}
+
+impl Default for ProtectionSetting {
+    fn default() -> Self {
+        ProtectionSetting {
+            secret: None,
+            unlock_max_age: default_unlock_max_age(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// What a role can do with the paths matched by an ACL rule, a higher permission includes the lower ones.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    #[serde(rename = "none")]
+    None,
+    /// shown in the file tree
+    #[serde(rename = "list")]
+    List,
+    /// shown in the file tree, and can be downloaded
+    #[serde(rename = "read")]
+    Read,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserConfig {
+    pub name: String,
+    /// Argon2 hash in PHC string format, like "$argon2id$v=19$m=19456,t=2,p=1$...".
+    pub password_hash: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AclRule {
+    /// Path prefix like "/team", the rules with the most specific path matching a path decide its permission.
+    pub path: String,
+    /// Roles granted by the rule, "*" is everyone including the anonymous.
+    pub roles: Vec<String>,
+    pub permission: Permission,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthSetting {
+    /// The key to sign the login sessions. When not provided, a random one is used, and all the sessions expire on restart.
+    pub secret: Option<String>,
+    #[serde(default = "default_session_max_age")]
+    pub session_max_age: u64,   // in seconds, default to 7 days
+    #[serde(default)]
+    pub users: Vec<UserConfig>,
+    /// Paths matched by no rule can be read by everyone.
+    #[serde(default)]
+    pub rules: Vec<AclRule>,
+}
+
+pub fn default_session_max_age() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+impl Default for AuthSetting {
+    fn default() -> Self {
+        AuthSetting {
+            secret: None,
+            session_max_age: default_session_max_age(),
+            users: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Shown in the download logs.
+    pub name: String,
+    /// SHA-256 of the key in lowercase hex, like the output of `printf %s "$KEY" | sha256sum`.
+    pub key_hash: String,
+    /// Path prefixes like "/releases" which the key can download from, all the paths when empty.
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// RFC 3339 time like "2025-01-01T00:00:00Z", the key never expires when not provided.
+    pub expires_at: Option<String>,
+    /// Requests per minute, unlimited when not provided.
+    pub rate_limit: Option<u32>,
+    /// Roles of the key for the ACL rules.
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ApiKeySetting {
+    /// A JSON file with an array of keys, loaded besides `keys`.
+    pub file: Option<String>,
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveSetting {
+    pub max_size: u64,  // in bytes, default to 10 GiB
+}
+
+impl Default for ArchiveSetting {
+    fn default() -> Self {
+        ArchiveSetting {
+            max_size: 10 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// The queue of download events, between the requests and the sinks. Each sink has its own queue.
+#[derive(Debug, Deserialize, Clone)]
+pub struct EventQueueSetting {
+    /// Events are dropped while the queue is full.
+    #[serde(default = "default_event_queue_capacity")]
+    pub capacity: usize,
+    /// The most events written to a sink at once.
+    #[serde(default = "default_event_batch_size")]
+    pub batch_size: usize,
+    /// in milliseconds, the longest time to wait for a batch to fill up
+    #[serde(default = "default_event_flush_interval")]
+    pub flush_interval: u64,
+    /// A failed batch is retried with exponential backoff, then dropped.
+    #[serde(default = "default_event_max_retries")]
+    pub max_retries: u32,
+}
+
+fn default_event_queue_capacity() -> usize {
+    10000
+}
+
+fn default_event_batch_size() -> usize {
+    500
+}
+
+fn default_event_flush_interval() -> u64 {
+    1000
+}
+
+fn default_event_max_retries() -> u32 {
+    3
+}
+
+impl Default for EventQueueSetting {
+    fn default() -> Self {
+        EventQueueSetting {
+            capacity: default_event_queue_capacity(),
+            batch_size: default_event_batch_size(),
+            flush_interval: default_event_flush_interval(),
+            max_retries: default_event_max_retries(),
+        }
+    }
+}
+
+/// How the log lines are written.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum LogFormat {
+    /// Human readable, with colors when written to a terminal.
+    #[default]
+    #[serde(rename = "pretty")]
+    Pretty,
+    /// One JSON object per line, with the fields of the spans.
+    #[serde(rename = "json")]
+    Json,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogSetting {
+    /// Like `info` or `rlist=debug,actix_web=warn`, overridden by `RUST_LOG`.
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Also append the log to this file.
+    pub file: Option<String>,
+}
+
+fn default_log_level() -> String {
+    "info".to_owned()
+}
+
+impl Default for LogSetting {
+    fn default() -> Self {
+        LogSetting {
+            level: default_log_level(),
+            format: LogFormat::default(),
+            file: None,
+        }
+    }
+}
+
+/// The events a webhook can subscribe to.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    /// A file under the watched paths is downloaded.
+    Download,
+    /// A drive fails and is marked unhealthy.
+    DriveUnhealthy,
+    /// A drive fails to load in a refresh.
+    RefreshFailed,
+    /// Files under the watched paths appear after a refresh.
+    NewFiles,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookConfig {
+    /// Events are posted to this url as JSON.
+    pub url: String,
+    /// When provided, the body is signed in `X-Rlist-Signature: sha256={hex of HMAC-SHA256}`.
+    pub secret: Option<String>,
+    pub events: Vec<WebhookEventKind>,
+    /// Like `/releases`, the downloads and new files are only posted under these paths. Empty for all the paths.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Failed deliveries are retried this many times, with exponential backoff.
+    #[serde(default = "default_webhook_max_retries")]
+    pub max_retries: u32,
+}
+
+fn default_webhook_max_retries() -> u32 {
+    3
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminConfig {
+    /// The `/admin` api requires `Authorization: Bearer {token}`.
+    pub token: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ConfigFile {
-    pub influx: Option<InfluxConfig>,
+    pub influx: Option<InfluxConfig>,           // kept for compatibility, the same as an `influx` sink
+    pub sinks: Option<Vec<SinkConfig>>,         // when not provided, downloads are not recorded except to `influx`
+    pub event_queue: Option<EventQueueSetting>, // when not provided, up to 10000 events are queued, and written 500 at a time
     pub drives: Vec<DriveConfig>,
     pub cache: Option<CacheSetting>,            // when not provided, cache will be set to default value
     pub captcha: Option<CaptchaConfig>,
+    pub token_store: Option<String>,            // path of the rotated refresh tokens, default to `token_store.json`
+    pub merge: Option<MergeSetting>,            // when not provided, conflicting files will be renamed
+    pub mirror: Option<MirrorSetting>,          // when not provided, mirrors will be selected randomly
+    pub health: Option<HealthSetting>,          // when not provided, drives will be checked every 60 seconds
+    pub admin: Option<AdminConfig>,             // when not provided, the admin api is disabled
+    pub archive: Option<ArchiveSetting>,        // when not provided, archives are limited to 10 GiB
+    pub visibility: Option<VisibilitySetting>,  // when not provided, everything is listed
+    pub protection: Option<ProtectionSetting>,  // when not provided, no directory is protected
+    pub auth: Option<AuthSetting>,              // when not provided, everything is public
+    pub api_keys: Option<ApiKeySetting>,        // when not provided, no API key is accepted
+    pub log: Option<LogSetting>,                // when not provided, `info` and above are printed to stdout
+    pub webhooks: Option<Vec<WebhookConfig>>,   // when not provided, no webhook is posted
+    pub trusted_proxies: Option<Vec<IpAddr>>,   // reverse proxies whose `X-Forwarded-For` is believed when limiting wrong passwords, none when not provided
+    pub public_url: Option<String>,             // like `https://example.com/`, where the api is served for links outside the site. when not provided, the feed is disabled
 }
 
 #[derive(Debug, Deserialize)]
@@ -144,4 +711,64 @@ mod tests {
             panic!("Expected Onedrive config");
         }
     }
+
+    #[test]
+    fn test_deserialize_drive_config_onedrive_sharepoint() {
+        let json = r#"
+        {
+            "drive_type": "onedrive",
+            "refresh_token": "someToken",
+            "client_id": "someId",
+            "client_secret": "secret",
+            "tenant": "contoso.onmicrosoft.com",
+            "drive": "/sites/someSite/drive",
+            "cloud": "china"
+        }
+        "#;
+
+        let config: Result<DriveConfig, _> = serde_json::from_str(json);
+
+        if let Ok(DriveConfig::Onedrive(config)) = config {
+            assert_eq!(config.tenant.as_deref(), Some("contoso.onmicrosoft.com"));
+            assert_eq!(config.drive.as_deref(), Some("/sites/someSite/drive"));
+            assert_eq!(config.cloud, Some(OnedriveCloud::China));
+            assert!(config.graph_url.is_none());
+        } else {
+            panic!("Expected Onedrive config");
+        }
+    }
+
+    #[test]
+    fn test_mirror_strategy_for_path() {
+        let json = r#"
+        {
+            "strategy": "weighted",
+            "rules": [
+                { "path": "/releases", "strategy": "round_robin" },
+                { "path": "/releases/nightly/", "strategy": "sticky" }
+            ]
+        }
+        "#;
+
+        let setting: MirrorSetting = serde_json::from_str(json).unwrap();
+
+        assert_eq!(setting.strategy_for("/docs/a.pdf"), SelectStrategy::Weighted);
+        assert_eq!(setting.strategy_for("/releases/v1.zip"), SelectStrategy::RoundRobin);
+        assert_eq!(setting.strategy_for("/releases/nightly/a.zip"), SelectStrategy::Sticky);
+        assert_eq!(setting.strategy_for("/releases2/a.zip"), SelectStrategy::Weighted);
+        assert_eq!(setting.unhealthy_cooldown, 300);
+    }
+
+    #[test]
+    fn test_example_config_parses() {
+        let config: ConfigFile = serde_json::from_str(include_str!("../../config.example.json")).unwrap();
+        let sinks = config.sinks.unwrap();
+        match &sinks[0] {
+            SinkConfig::Influx(influx) => {
+                assert_eq!(influx.bucket.as_deref(), Some("my-bucket"));
+                assert_eq!(influx.precision, InfluxPrecision::Milliseconds);
+            }
+            _ => panic!("Expected influx sink"),
+        }
+    }
 }
\ No newline at end of file
@@ -1,31 +1,57 @@
 use std::error::Error;
 use std::fs::File;
-use crate::config_loader::{Config, CONFIG_PATH};
-use crate::config_loader::config_struct::{CacheSetting, ConfigFile};
+use reqwest::Url;
+use crate::config_loader::{Config, CONFIG_PATH, TOKEN_STORE_PATH};
+use crate::config_loader::config_struct::{CacheSetting, ConfigFile, default_change_history, default_link_max_age, SinkConfig};
 
 pub fn load_config() -> Result<Config, Box<dyn Error>> {
     let config_file = File::open(CONFIG_PATH)?;
     let config_file: ConfigFile = serde_json::from_reader(config_file)?;
+    let token_store = config_file.token_store.unwrap_or_else(|| TOKEN_STORE_PATH.to_owned());
 
     // set default value for cache
-    match config_file.cache {
-        None => {
-            Ok(Config {
-                influx: config_file.influx,
-                drives: config_file.drives,
-                cache: CacheSetting {
-                    refresh_interval: 600,
-                },
-                captcha: config_file.captcha,
-            })
-        },
-        Some(cache) => {
-            Ok(Config {
-                influx: config_file.influx,
-                drives: config_file.drives,
-                cache,
-                captcha: config_file.captcha,
-            })
-        }
+    let cache = config_file.cache.unwrap_or(CacheSetting {
+        refresh_interval: 600,
+        link_max_age: default_link_max_age(),
+        change_history: default_change_history(),
+    });
+
+    let mut sinks = config_file.sinks.unwrap_or_default();
+    if let Some(influx) = config_file.influx {
+        sinks.push(SinkConfig::Influx(influx));
     }
+
+    let public_url = match config_file.public_url {
+        Some(url) => {
+            let mut url = Url::parse(&url).map_err(|e| format!("Invalid public_url {}: {}", url, e))?;
+            // the api is joined to the url, so it should be a directory
+            if !url.path().ends_with('/') {
+                url.set_path(&format!("{}/", url.path()));
+            }
+            Some(url)
+        }
+        None => None,
+    };
+
+    Ok(Config {
+        sinks,
+        event_queue: config_file.event_queue.unwrap_or_default(),
+        drives: config_file.drives,
+        cache,
+        captcha: config_file.captcha,
+        token_store,
+        merge: config_file.merge.unwrap_or_default(),
+        mirror: config_file.mirror.unwrap_or_default(),
+        health: config_file.health.unwrap_or_default(),
+        admin: config_file.admin,
+        archive: config_file.archive.unwrap_or_default(),
+        visibility: config_file.visibility.unwrap_or_default(),
+        protection: config_file.protection.unwrap_or_default(),
+        auth: config_file.auth.unwrap_or_default(),
+        api_keys: config_file.api_keys.unwrap_or_default(),
+        log: config_file.log.unwrap_or_default(),
+        webhooks: config_file.webhooks.unwrap_or_default(),
+        trusted_proxies: config_file.trusted_proxies.unwrap_or_default(),
+        public_url,
+    })
 }
\ No newline at end of file
@@ -1,13 +1,33 @@
 pub mod config_struct;
 pub mod load_config_file;
 
-use crate::config_loader::config_struct::{CacheSetting, CaptchaConfig, DriveConfig, InfluxConfig};
+use std::net::IpAddr;
+use reqwest::Url;
+
+use crate::config_loader::config_struct::{AdminConfig, ApiKeySetting, ArchiveSetting, AuthSetting, CacheSetting, CaptchaConfig, DriveConfig, EventQueueSetting, HealthSetting, LogSetting, MergeSetting, MirrorSetting, ProtectionSetting, SinkConfig, VisibilitySetting, WebhookConfig};
 
 pub const CONFIG_PATH: &str = "config.json";
+pub const TOKEN_STORE_PATH: &str = "token_store.json";
 
 pub struct Config {
-    pub influx: Option<InfluxConfig>,
+    pub sinks: Vec<SinkConfig>,
+    pub event_queue: EventQueueSetting,
     pub drives: Vec<DriveConfig>,
     pub cache: CacheSetting,
     pub captcha: Option<CaptchaConfig>,
+    pub token_store: String,
+    pub merge: MergeSetting,
+    pub mirror: MirrorSetting,
+    pub health: HealthSetting,
+    pub admin: Option<AdminConfig>,
+    pub archive: ArchiveSetting,
+    pub visibility: VisibilitySetting,
+    pub protection: ProtectionSetting,
+    pub auth: AuthSetting,
+    pub api_keys: ApiKeySetting,
+    pub log: LogSetting,
+    pub webhooks: Vec<WebhookConfig>,
+    pub trusted_proxies: Vec<IpAddr>,
+    /// always ends with `/`
+    pub public_url: Option<Url>,
 }
\ No newline at end of file
@@ -1,10 +1,79 @@
-use crate::vfs::combine::{CombinableVfsDir, CombinableVfsFile};
-use crate::vfs::{VfsDir, VfsEntry, VfsFile};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use crate::config_loader::config_struct::DriveConfig;
+use crate::driver::token_store::TokenStore;
+use crate::vfs::combine::{CombinableVfsDir, DriveTag};
+use crate::vfs::visibility::PathFilter;
 mod onedrive;
+pub mod token_store;
 
 /// # OneDrive Driver
 /// To use onedrive as a VFS, you need to provide a refresh token, a client id and a client secret. (*refer to `OnedriveConfig` in `config_struct.rs`*)
-pub(crate) use onedrive::OneDriveDriver;
+pub(crate) use onedrive::{OneDriveAccount, OneDriveDriver};
+
+/// # Drive
+/// A configured cloud drive. Unlike the drivers, it is created once and kept by `DriveWheel` across refreshes.
+pub struct Drive {
+    tag: Arc<DriveTag>,
+    kind: DriveKind,
+    /// `hide` and `unlisted` patterns of the drive
+    filter: PathFilter,
+}
+
+enum DriveKind {
+    Onedrive(Arc<OneDriveAccount>),
+}
+
+impl Drive {
+    /// `index` is the position of the drive in config, which names the drive if no name is given.
+    /// Fails if the `hide` or `unlisted` patterns are invalid.
+    pub fn new(index: usize, config: DriveConfig, token_store: Arc<TokenStore>, link_max_age: Duration) -> Result<Self, String> {
+        match config {
+            DriveConfig::Onedrive(config) => {
+                let tag = Arc::new(DriveTag {
+                    name: config.name.clone().unwrap_or_else(|| format!("drive{}", index)),
+                    priority: config.priority.unwrap_or(0),
+                });
+                let filter = PathFilter::new(
+                    config.hide.as_deref().unwrap_or_default(),
+                    config.unlisted.as_deref().unwrap_or_default(),
+                ).map_err(|e| format!("{}: {}", tag.name, e))?;
+                let account = OneDriveAccount::new(config, token_store, link_max_age, tag.clone());
+                Ok(Drive {
+                    tag,
+                    kind: DriveKind::Onedrive(Arc::new(account)),
+                    filter,
+                })
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.tag.name
+    }
+
+    /// Check whether the drive still works.
+    pub async fn probe(&self) -> Result<(), String> {
+        match &self.kind {
+            DriveKind::Onedrive(account) => account.probe().await,
+        }
+    }
+
+    /// When the current access token of the drive expires, `None` if it has no token yet.
+    pub async fn token_expires_at(&self) -> Option<SystemTime> {
+        match &self.kind {
+            DriveKind::Onedrive(account) => account.token_expires_at().await,
+        }
+    }
+
+    /// Build the driver and convert it into VFS directory, with the `hide` and `unlisted` patterns of the drive applied.
+    pub async fn load(&self) -> Result<CombinableVfsDir, String> {
+        let dir = match &self.kind {
+            DriveKind::Onedrive(account) => OneDriveDriver::new(account).await.map(|driver| driver.into_combinable())?,
+        };
+        Ok(dir.apply_filter(&self.filter, ""))
+    }
+}
 
 /// # Cloud Driver
 /// The cloud driver is a driver that can be used to access a cloud storage service, then use the cloud storage service as a virtual file system(VFS).
@@ -14,31 +83,3 @@ pub trait CloudDriver<Config: Send + Sync> {
     fn into_combinable(self) -> CombinableVfsDir;
     async fn new(config: &Config) -> Result<Self, String> where Self: Sized;
 }
-
-pub trait CloudDriverFile: VfsFile + Sized {
-    fn into_combinable(self) -> CombinableVfsFile {
-        CombinableVfsFile::new(
-            vec![self.on_download()],
-            self.name().to_string(),
-            self.size(),
-            self.last_modified(),
-        )
-    }
-}
-
-pub trait CloudDriverDir<File: CloudDriverFile>: VfsDir<File> + Sized + Clone {
-    fn into_combinable(self) -> CombinableVfsDir {
-        let list = self.list();
-        let mut dirs = Vec::new();
-        let mut files = Vec::new();
-        list.into_iter().for_each(|entry| {
-            match entry {
-                VfsEntry::File(file) => files.push(file.into_combinable()),
-                VfsEntry::Dir(dir) => dirs.push(dir.into_combinable())
-            }
-        });
-        let size = self.size();
-        let name = self.name().to_string();
-        CombinableVfsDir::new(name, dirs, files, size)
-    }
-}
\ No newline at end of file
@@ -0,0 +1,84 @@
+use crate::config_loader::config_struct::{OnedriveCloud, OnedriveConfig};
+
+/// # OneDrive Endpoints
+/// The urls of the login endpoint and the graph api depend on the national cloud, the tenant and the target drive.
+pub struct OneDriveEndpoints {
+    /// like `https://login.microsoftonline.com/common/oauth2/v2.0/token`
+    token_url: String,
+    /// like `https://graph.microsoft.com/v1.0`
+    graph_url: String,
+    /// like `/me/drive`
+    drive_path: String,
+}
+
+impl OnedriveCloud {
+    /// The default base urls of the login endpoint and the graph api.
+    fn base_urls(&self) -> (&'static str, &'static str) {
+        match self {
+            OnedriveCloud::Global => ("https://login.microsoftonline.com", "https://graph.microsoft.com"),
+            OnedriveCloud::China => ("https://login.chinacloudapi.cn", "https://microsoftgraph.chinacloudapi.cn"),
+            OnedriveCloud::UsGov => ("https://login.microsoftonline.us", "https://graph.microsoft.us"),
+            OnedriveCloud::UsGovDod => ("https://login.microsoftonline.us", "https://dod-graph.microsoft.us"),
+        }
+    }
+}
+
+impl OneDriveEndpoints {
+    pub fn new(config: &OnedriveConfig) -> Self {
+        let (auth_base, graph_base) = config.cloud.unwrap_or(OnedriveCloud::Global).base_urls();
+        let auth_base = config.auth_url.as_deref().unwrap_or(auth_base).trim_end_matches('/');
+        let graph_base = config.graph_url.as_deref().unwrap_or(graph_base).trim_end_matches('/');
+        let tenant = config.tenant.as_deref().unwrap_or("common");
+        let drive_path = config.drive.as_deref().unwrap_or("/me/drive").trim_end_matches('/');
+        OneDriveEndpoints {
+            token_url: format!("{}/{}/oauth2/v2.0/token", auth_base, tenant),
+            graph_url: format!("{}/v1.0", graph_base),
+            drive_path: format!("/{}", drive_path.trim_start_matches('/')),
+        }
+    }
+
+    pub fn token_url(&self) -> &str {
+        &self.token_url
+    }
+
+    /// The target drive, which tells the drive id.
+    pub fn drive_url(&self) -> String {
+        format!("{}{}", self.graph_url, self.drive_path)
+    }
+
+    pub fn list_url(&self, drive_id: &str, dir_id: &str) -> String {
+        format!("{}/drives/{}/items/{}/children", self.graph_url, drive_id, dir_id)
+    }
+
+    pub fn download_url_url(&self, drive_id: &str, item_id: &str) -> String {
+        format!("{}/drives/{}/items/{}?select=@microsoft.graph.downloadUrl", self.graph_url, drive_id, item_id)
+    }
+
+    /// Like `download_url_url`, but the item is addressed by its path (like `/folder/file`), which is percent-encoded.
+    pub fn path_download_url_url(&self, drive_id: &str, path: &str) -> String {
+        let path: String = path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| format!("/{}", encode_segment(segment)))
+            .collect();
+        format!("{}/drives/{}/root:{}?select=@microsoft.graph.downloadUrl", self.graph_url, drive_id, path)
+    }
+}
+
+/// Percent-encode all the bytes of a path segment except the unreserved characters of RFC 3986.
+fn encode_segment(segment: &str) -> String {
+    segment.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_is_encoded() {
+        assert_eq!(encode_segment("a b#?%.txt"), "a%20b%23%3F%25.txt");
+        assert_eq!(encode_segment("文"), "%E6%96%87");
+    }
+}
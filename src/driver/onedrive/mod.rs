@@ -0,0 +1,439 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+use tokio::sync::{OnceCell, Semaphore};
+use tracing::warn;
+use crate::config_loader::config_struct::{OnedriveConfig};
+use crate::driver::CloudDriver;
+use crate::driver::token_store::TokenStore;
+use crate::vfs::combine::{CombinableVfsDir, CombinableVfsFile, ContentHash, DownloadLink, DriveTag, LinkResolver};
+use std::marker::Send;
+use endpoint::OneDriveEndpoints;
+use retry::get_with_retry;
+use token::OneDriveTokenManager;
+
+mod endpoint;
+mod retry;
+mod token;
+
+#[async_trait::async_trait]
+impl CloudDriver<Arc<OneDriveAccount>> for OneDriveDriver {
+    fn into_combinable(self) -> CombinableVfsDir {
+        self.root.into_combinable(&self.account, &self.drive_id)
+    }
+
+    async fn new(account: &Arc<OneDriveAccount>) -> Result<Self, String> {
+        let drive_id = account.drive_id().await?;
+        let tree_builder = OneDriveTreeBuilder::new(account.clone(), drive_id.clone());
+        let root = tree_builder.build_tree(
+            "root".to_owned(),
+            String::new(),
+            "root".to_owned(),
+            0,
+            SystemTime::now(),
+        ).await;
+        let (root, failures) = root;
+        for failure in failures.iter() {
+            warn!("Failed to build {} in the tree {}: {}", failure.path, drive_id, failure.reason);
+        }
+        // an incomplete tree would show the files of the folder as removed, so the last tree is kept instead
+        if let Some(failure) = failures.iter().find(|failure| failure.incomplete) {
+            return Err(format!("Failed to list {}: {}", failure.path, failure.reason));
+        }
+        Ok(OneDriveDriver {
+            root: match root {
+                OneDriveItem::Folder(folder) => folder,
+                _ => return Err("Failed to request the root folder".to_owned()),
+            },
+            account: account.clone(),
+            drive_id,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+/// Response json when request the target drive (like `/me/drive`).
+struct MyDrive {
+    id: String,
+}
+
+/// # OneDrive Account
+/// A onedrive account lives as long as the `DriveWheel`, while `OneDriveDriver` is rebuilt on every refresh.
+/// So the state which should survive refreshes (like the access token and the drive id) is kept here.
+pub struct OneDriveAccount {
+    endpoints: OneDriveEndpoints,
+    token: OneDriveTokenManager,
+    drive_id: OnceCell<String>,
+    /// `@microsoft.graph.downloadUrl` expires after about an hour, older ones will be resolved again.
+    link_max_age: Duration,
+    tag: Arc<DriveTag>,
+    canary: Option<String>,
+}
+
+impl OneDriveAccount {
+    pub fn new(config: OnedriveConfig, token_store: Arc<TokenStore>, link_max_age: Duration, tag: Arc<DriveTag>) -> Self {
+        let endpoints = OneDriveEndpoints::new(&config);
+        let canary = config.canary.clone();
+        OneDriveAccount {
+            token: OneDriveTokenManager::new(tag.name.clone(), config, &endpoints, token_store),
+            endpoints,
+            drive_id: OnceCell::new(),
+            link_max_age,
+            tag,
+            canary,
+        }
+    }
+
+    /// Check whether the account still works, it should be quick so nothing is retried.
+    /// The drive is requested, then if there is a canary file, its fresh download url is checked by `HEAD`.
+    pub async fn probe(&self) -> Result<(), String> {
+        let drive_id = self.drive_id().await?;
+        let res = self.token.get(&self.endpoints.drive_url()).await?;
+        if !res.status().is_success() {
+            return Err(format!("Failed to request the drive: {}", res.status()));
+        }
+        let canary = match &self.canary {
+            Some(canary) => canary,
+            None => return Ok(()),
+        };
+        let res = self.token.get(&self.endpoints.path_download_url_url(&drive_id, canary)).await?;
+        let url = match res.json::<ResponseDownloadUrl>().await {
+            Ok(body) => body.download_url,
+            Err(_) => return Err(format!("Failed to get the download url of canary {}", canary)),
+        };
+        let res = self.token.client().head(url).send().await
+            .map_err(|e| format!("Failed to download canary {}: {}", canary, e))?;
+        if !res.status().is_success() {
+            return Err(format!("Failed to download canary {}: {}", canary, res.status()));
+        }
+        Ok(())
+    }
+
+    /// When the current access token expires.
+    pub async fn token_expires_at(&self) -> Option<SystemTime> {
+        self.token.expires_at().await
+    }
+
+    /// The drive id never changes, so it is only requested once.
+    async fn drive_id(&self) -> Result<String, String> {
+        self.drive_id.get_or_try_init(|| get_my_od_id(self)).await.cloned()
+    }
+}
+
+async fn get_my_od_id(account: &OneDriveAccount) -> Result<String, String> {
+    let res = get_with_retry(&account.token, &account.endpoints.drive_url()).await?;
+    match res.json::<MyDrive>().await {
+        Ok(body) => Ok(body.id),
+        Err(_) => Err("Failed to get drive id".to_owned()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+/// the file or folder item in the response json.
+struct ResponseItem {
+    id: String,
+    name: String,
+    size: i64,
+    #[serde(rename = "@microsoft.graph.downloadUrl")]
+    file_download_url: Option<String>,
+    file: Option<ResponseFileFacet>,
+    // only the presence of `folder` is used to tell folders from files
+    folder: Option<IgnoredAny>,
+    #[serde(rename = "lastModifiedDateTime")]
+    last_modified_date_time: String,
+}
+
+#[derive(Debug, Deserialize)]
+/// the `file` facet of a file item.
+struct ResponseFileFacet {
+    hashes: Option<ResponseHashes>,
+}
+
+#[derive(Debug, Deserialize)]
+/// the hashes of a file, *personal onedrive may not have `sha1Hash`*.
+struct ResponseHashes {
+    #[serde(rename = "sha1Hash")]
+    sha1_hash: Option<String>,
+    #[serde(rename = "quickXorHash")]
+    quick_xor_hash: Option<String>,
+}
+
+impl ResponseFileFacet {
+    fn into_hash(self) -> ContentHash {
+        match self.hashes {
+            Some(hashes) => ContentHash {
+                sha1: hashes.sha1_hash.map(|hash| hash.to_lowercase()),
+                quick_xor: hashes.quick_xor_hash,
+                etag: None,
+            },
+            None => ContentHash::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+/// the response json when request the graphql api.
+struct ResponseList {
+    value: Vec<ResponseItem>,
+    /// the graph api pages the children of a large folder, the next page should be requested from this url
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+}
+
+/// Request all the children of a folder, following the `@odata.nextLink` until the last page.
+async fn request_list(drive_id: &str, dir_id: &str, account: &OneDriveAccount) -> Result<Vec<ResponseItem>, String> {
+    let mut items = Vec::new();
+    let mut url = account.endpoints.list_url(drive_id, dir_id);
+    loop {
+        let res = get_with_retry(&account.token, &url).await?;
+        if !res.status().is_success() {
+            return Err(format!("Failed to request list: {}", res.status()));
+        }
+        let body = match res.json::<ResponseList>().await {
+            Ok(body) => body,
+            Err(_) => return Err("Failed to parse response".to_owned()),
+        };
+        items.extend(body.value);
+        match body.next_link {
+            Some(next_link) => url = next_link,
+            None => return Ok(items),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+/// the response json when request a fresh download url of an item.
+struct ResponseDownloadUrl {
+    #[serde(rename = "@microsoft.graph.downloadUrl")]
+    download_url: String,
+}
+
+/// Resolve a fresh download url of a onedrive file, since `@microsoft.graph.downloadUrl` will expire.
+struct OneDriveLinkResolver {
+    account: Arc<OneDriveAccount>,
+    drive_id: String,
+    item_id: String,
+}
+
+#[async_trait::async_trait]
+impl LinkResolver for OneDriveLinkResolver {
+    async fn resolve(&self) -> Result<String, String> {
+        let url = self.account.endpoints.download_url_url(&self.drive_id, &self.item_id);
+        let res = self.account.token.get(&url).await?;
+        match res.json::<ResponseDownloadUrl>().await {
+            Ok(body) => Ok(body.download_url),
+            Err(_) => Err("Failed to parse download url".to_owned()),
+        }
+    }
+}
+
+/// internal struct to represent a file in onedrive
+struct OneDriveFile {
+    id: String,
+    name: String,
+    size: i64,
+    last_modified: SystemTime,
+    download_url: String,
+    fetched_at: SystemTime,
+    hash: ContentHash,
+}
+impl OneDriveFile {
+    fn into_combinable(self, account: &Arc<OneDriveAccount>, drive_id: &str) -> CombinableVfsFile {
+        let resolver = OneDriveLinkResolver {
+            account: account.clone(),
+            drive_id: drive_id.to_owned(),
+            item_id: self.id,
+        };
+        CombinableVfsFile::new(
+            vec![DownloadLink::expiring(self.download_url, self.fetched_at, Arc::new(resolver), account.link_max_age, account.tag.clone())],
+            self.name,
+            self.size as u64,
+            self.last_modified,
+            self.hash,
+        )
+    }
+}
+
+/// internal struct to represent a folder in onedrive
+struct OneDriveFolder {
+    id: String,
+    name: String,
+    size: i64,
+    last_modified: SystemTime,
+    children: Vec<OneDriveItem>,
+}
+impl OneDriveFolder {
+    fn into_combinable(self, account: &Arc<OneDriveAccount>, drive_id: &str) -> CombinableVfsDir {
+        let (files, dirs): (Vec<_>, Vec<_>) = self.children.into_iter()
+            .partition(|item| matches!(item, OneDriveItem::File(_)));
+        let files = files.into_iter().map(|item| {
+            match item {
+                OneDriveItem::File(file) => file.into_combinable(account, drive_id),
+                _ => panic!("Unexpected item type"),
+            }
+        }).collect();
+        let dirs = dirs.into_iter().map(|item| {
+            match item {
+                OneDriveItem::Folder(folder) => folder.into_combinable(account, drive_id),
+                _ => panic!("Unexpected item type"),
+            }
+        }).collect();
+        CombinableVfsDir::new(
+            self.name,
+            dirs,
+            files,
+            self.size as u64,
+        )
+    }
+}
+
+/// internal enum to represent a file or a folder in onedrive
+enum OneDriveItem {
+    File(OneDriveFile),
+    Folder(OneDriveFolder),
+    Unknown,
+}
+
+impl ResponseItem {
+    /// convert response item into `OneDriveItem`
+    pub fn into_item(self) -> OneDriveItem {
+        match (self.file, self.folder, self.file_download_url) {
+            (Some(file), None, Some(url)) => OneDriveItem::File(OneDriveFile {
+                hash: file.into_hash(),
+                id: self.id,
+                name: self.name,
+                size: self.size,
+                download_url: url,
+                fetched_at: SystemTime::now(),
+                last_modified: DateTime::<Utc>::from(
+                    DateTime::parse_from_rfc3339(
+                        self.last_modified_date_time.as_str()
+                    ).unwrap()
+                ).into(),
+            }),
+            (None, Some(_), None) => OneDriveItem::Folder(OneDriveFolder {
+                id: self.id,
+                name: self.name,
+                size: self.size,
+                children: Vec::new(),
+                last_modified: DateTime::<Utc>::from(
+                    DateTime::parse_from_rfc3339(
+                        self.last_modified_date_time.as_str()
+                    ).unwrap()
+                ).into(),
+            }),
+            _ => OneDriveItem::Unknown,
+        }
+    }
+}
+
+/// A folder or an item which can not be put into the tree.
+pub struct TreeFailure {
+    /// the path in the drive, like `/folder/file`
+    pub path: String,
+    pub reason: String,
+    /// a folder which can not be listed, so the tree misses its content
+    pub incomplete: bool,
+}
+
+type RequestTreeResult = (OneDriveItem, Vec<TreeFailure>);   // 1st: root, 2nd: failures
+
+/// At most this many list requests of one drive are in flight at the same time.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// internal struct to build the tree of the onedrive
+struct OneDriveTreeBuilder {
+    account: Arc<OneDriveAccount>,
+    drive_id: String,
+    permits: Semaphore,
+}
+
+impl OneDriveTreeBuilder {
+    fn build_tree(&self, dir_id: String, path: String, name: String, size: i64, last_modified_time: SystemTime) -> Pin<Box<dyn Future<Output=RequestTreeResult> + '_ + Send>> {
+        // When use recursive async function, return type must be `Pin<Box<dyn Future<Output=...> + '_ + Send>>`.
+        Box::pin(async move {
+            // request the graphql api, the permit is released once the list is got
+            let res = {
+                let _permit = self.permits.acquire().await.expect("Semaphore is never closed");
+                request_list(self.drive_id.as_str(), dir_id.as_str(), &self.account).await
+            };
+            let list = match res {
+                Ok(list) => list,
+                Err(reason) => return (OneDriveItem::Unknown, vec![TreeFailure { path, reason, incomplete: true }]),
+            };
+
+            // initial failures and two vectors to store files and folders
+            let mut failures = Vec::new();
+            let mut folders = Vec::new();
+            let mut files = Vec::new();
+
+            // divide the items in the list into files and folders
+            list.into_iter().for_each(|item| {
+                let item_path = format!("{}/{}", path, item.name);
+                let item = item.into_item();
+                match item {
+                    OneDriveItem::Unknown => failures.push(TreeFailure {
+                        path: item_path,
+                        reason: "Unsupported item type".to_owned(),
+                        incomplete: false,
+                    }),
+                    OneDriveItem::File(file) => files.push(OneDriveItem::File(file)),
+                    OneDriveItem::Folder(folder) => folders.push(
+                        OneDriveItem::Folder(folder)
+                    ),
+                }
+            });
+
+            // graphql api will only return the id of the folders, so we need to build the tree recursively
+            let folders = folders.into_iter().map(|folder| {
+                let folder = match folder {
+                    OneDriveItem::Folder(folder) => folder,
+                    _ => panic!("Unexpected item type"),
+                };
+                let folder_path = format!("{}/{}", path, folder.name);
+                self.build_tree(folder.id, folder_path, folder.name, folder.size, folder.last_modified)
+            }).collect::<Vec<_>>();
+
+            // wait for all the folders to be built
+            let folders = futures::future::join_all(folders).await;
+            // collect failures, the folders failed to be listed are dropped, and the whole tree is refused
+            let folders = folders.into_iter().filter_map(|(folder, folder_failures)| {
+                failures.extend(folder_failures);
+                match folder {
+                    OneDriveItem::Unknown => None,
+                    folder => Some(folder),
+                }
+            }).collect::<Vec<_>>();
+
+            // process files
+            let children = files.into_iter().chain(folders).collect();
+
+            // return the result
+            (OneDriveItem::Folder(OneDriveFolder {
+                id: dir_id,
+                name,
+                size,
+                last_modified: last_modified_time,
+                children,
+            }), failures)
+        })
+    }
+
+    pub fn new(account: Arc<OneDriveAccount>, drive_id: String) -> Self {
+        OneDriveTreeBuilder {
+            account,
+            drive_id,
+            permits: Semaphore::new(MAX_CONCURRENT_REQUESTS),
+        }
+    }
+}
+
+pub struct OneDriveDriver {
+    root: OneDriveFolder,
+    account: Arc<OneDriveAccount>,
+    drive_id: String,
+}
\ No newline at end of file
@@ -0,0 +1,52 @@
+use std::time::Duration;
+use reqwest::{Response, StatusCode};
+use reqwest::header::RETRY_AFTER;
+use tracing::warn;
+use crate::driver::onedrive::token::OneDriveTokenManager;
+
+/// How many times a request will be retried before giving up.
+const MAX_RETRIES: u32 = 5;
+/// The first backoff, it will be doubled after each retry.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+/// Never wait longer than this, even if the graph api asks to.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Send an authorized `GET` request to the graph api, and retry the transient failures.
+/// - `429 Too Many Requests` and `503 Service Unavailable` are retried after `Retry-After` (or the backoff if absent).
+/// - other `5xx` responses and network errors are retried with exponential backoff.
+/// - the other responses are returned as is.
+pub async fn get_with_retry(token: &OneDriveTokenManager, url: &str) -> Result<Response, String> {
+    let mut attempt = 0;
+    loop {
+        let backoff = (BASE_BACKOFF * 2u32.pow(attempt)).min(MAX_BACKOFF);
+        let wait = match token.get(url).await {
+            Ok(res) if is_throttled(res.status()) => {
+                retry_after(&res).unwrap_or(backoff)
+            }
+            Ok(res) if res.status().is_server_error() => backoff,
+            Ok(res) => return Ok(res),
+            Err(e) if attempt < MAX_RETRIES => {
+                warn!("{}, retry in {:?}", e, backoff);
+                backoff
+            }
+            Err(e) => return Err(e),
+        };
+        if attempt >= MAX_RETRIES {
+            return Err(format!("Gave up {} after {} retries", url, MAX_RETRIES));
+        }
+        tokio::time::sleep(wait.min(MAX_BACKOFF)).await;
+        attempt += 1;
+    }
+}
+
+fn is_throttled(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// The graph api gives `Retry-After` in seconds.
+fn retry_after(res: &Response) -> Option<Duration> {
+    res.headers().get(RETRY_AFTER)?
+        .to_str().ok()?
+        .trim().parse::<u64>().ok()
+        .map(Duration::from_secs)
+}
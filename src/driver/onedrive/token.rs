@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tracing::warn;
+use crate::config_loader::config_struct::OnedriveConfig;
+use crate::driver::onedrive::endpoint::OneDriveEndpoints;
+use crate::driver::token_store::TokenStore;
+use crate::service::metrics::METRICS;
+
+/// Renew the access token a bit earlier than it really expires, so that a long request will not fail halfway.
+const RENEW_BEFORE_EXPIRY: Duration = Duration::from_secs(300);
+
+#[allow(unused)]
+#[derive(Debug, Deserialize)]
+/// The response json when request the token url.
+struct AccessTokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: u64,
+    scope: String,
+    /// Microsoft rotates the refresh token, the new one should be used in the next refresh.
+    refresh_token: Option<String>,
+}
+
+struct CachedToken {
+    access_token: String,
+    renew_at: Instant,
+    expires_at: SystemTime,
+}
+
+/// # OneDrive Token Manager
+/// Caches the access token of a onedrive account and renews it shortly before it expires.
+/// All the requests to the graph api of an account should get their token from here.
+pub struct OneDriveTokenManager {
+    /// the name of the drive, to label the metrics
+    drive: String,
+    config: OnedriveConfig,
+    token_url: String,
+    token_store: Arc<TokenStore>,
+    client: reqwest::Client,
+    // `tokio::sync::Mutex` is held while renewing, so that concurrent requests will not renew the token at the same time.
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl OneDriveTokenManager {
+    pub fn new(drive: String, config: OnedriveConfig, endpoints: &OneDriveEndpoints, token_store: Arc<TokenStore>) -> Self {
+        OneDriveTokenManager {
+            drive,
+            config,
+            token_url: endpoints.token_url().to_owned(),
+            token_store,
+            client: reqwest::Client::new(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Get a valid access token, renew it if it is (nearly) expired.
+    pub async fn access_token(&self) -> Result<String, String> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if Instant::now() < token.renew_at {
+                return Ok(token.access_token.clone());
+            }
+        }
+        let token = self.fetch_access_token().await
+            .inspect_err(|_| METRICS.token_refresh_failures.with_label_values(&[&self.drive]).inc())?;
+        let access_token = token.access_token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    /// The client of the account, to be reused by the requests which need no token.
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// When the cached access token expires, `None` if no token is cached.
+    pub async fn expires_at(&self) -> Option<SystemTime> {
+        self.cached.lock().await.as_ref().map(|token| token.expires_at)
+    }
+
+    /// Drop the cached token if it is still `rejected`, so that the next `access_token` will renew it.
+    async fn invalidate(&self, rejected: &str) {
+        let mut cached = self.cached.lock().await;
+        if cached.as_ref().is_some_and(|token| token.access_token == rejected) {
+            *cached = None;
+        }
+    }
+
+    /// Send an authorized `GET` request to the graph api.
+    /// If the graph api responds `401 Unauthorized`, the token will be renewed and the request will be retried once.
+    pub async fn get(&self, url: &str) -> Result<Response, String> {
+        let token = self.access_token().await?;
+        let res = self.send_get(url, &token).await?;
+        if res.status() != StatusCode::UNAUTHORIZED {
+            return Ok(res);
+        }
+        warn!("Access token was rejected by graph api, renew and retry once.");
+        self.invalidate(&token).await;
+        let token = self.access_token().await?;
+        self.send_get(url, &token).await
+    }
+
+    async fn send_get(&self, url: &str, token: &str) -> Result<Response, String> {
+        self.client.get(url)
+            .bearer_auth(token)
+            .send().await
+            .map_err(|e| format!("Failed to request {}: {}", url, e))
+    }
+
+    async fn fetch_access_token(&self) -> Result<CachedToken, String> {
+        let config = &self.config;
+        let refresh_token = self.token_store.get(&config.refresh_token);
+        let res = self.client.post(&self.token_url)
+            .form(&[
+                ("client_id", config.client_id.as_str()),
+                ("refresh_token", refresh_token.as_str()),
+                ("requested_token_use", "on_behalf_of"),
+                ("client_secret", config.client_secret.as_str()),
+                ("grant_type", "refresh_token"),
+            ])
+            .send().await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("Failed to fetch access token: {}", e))?;
+        let body = res.json::<AccessTokenResponse>().await
+            .map_err(|e| format!("Failed to parse access token: {}", e))?;
+        if let Some(rotated) = body.refresh_token {
+            self.token_store.set(&config.refresh_token, rotated);
+        }
+        let lifetime = Duration::from_secs(body.expires_in).saturating_sub(RENEW_BEFORE_EXPIRY);
+        Ok(CachedToken {
+            access_token: body.access_token,
+            renew_at: Instant::now() + lifetime,
+            expires_at: SystemTime::now() + Duration::from_secs(body.expires_in),
+        })
+    }
+}
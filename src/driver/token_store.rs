@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{error, warn};
+
+/// # Token Store
+/// OAuth providers (like Microsoft) rotate the refresh token on every refresh, and the old one will eventually expire.
+/// The token store keeps the latest refresh token of each account in a json file, so that it can survive restarts.
+///
+/// Tokens are keyed by the refresh token written in the config file, so if the user replaces the token in config,
+/// the rotated chain of the old one will no longer be used.
+pub struct TokenStore {
+    path: PathBuf,
+    tokens: Mutex<HashMap<String, String>>,
+}
+
+impl TokenStore {
+    /// Load the token store from `path`. If the file does not exist or is broken, an empty store will be used.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let tokens = match fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                warn!("Token store {} is broken, ignored: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        TokenStore {
+            path,
+            tokens: Mutex::new(tokens),
+        }
+    }
+
+    /// Get the latest refresh token derived from `origin`, or `origin` itself if it has never been rotated.
+    pub fn get(&self, origin: &str) -> String {
+        let tokens = self.tokens.lock().unwrap();
+        tokens.get(origin).cloned().unwrap_or_else(|| origin.to_owned())
+    }
+
+    /// Record a rotated refresh token and write the store back to disk.
+    pub fn set(&self, origin: &str, token: String) {
+        let mut tokens = self.tokens.lock().unwrap();
+        if tokens.get(origin) == Some(&token) {
+            return;
+        }
+        tokens.insert(origin.to_owned(), token);
+        if let Err(e) = self.persist(&tokens) {
+            error!("Failed to write token store {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Write to a temporary file first, then rename it, so that a crash will never leave a half written store.
+    fn persist(&self, tokens: &HashMap<String, String>) -> std::io::Result<()> {
+        let content = serde_json::to_vec_pretty(tokens)?;
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
@@ -1,10 +1,28 @@
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use actix_web::{App, HttpServer, web};
+use actix_web::dev::Service;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info_span, Instrument};
+use uuid::Uuid;
 use crate::config_loader::{Config, load_config_file};
+use crate::config_loader::config_struct::{ArchiveSetting, MirrorSetting};
+use crate::driver::token_store::TokenStore;
+use crate::service::api_key::ApiKeys;
+use crate::service::auth::Auth;
 use crate::service::captcha::{load_captcha, Verify};
+use crate::service::drive_health::DriveHealth;
 use crate::service::drive_whell::DriveWheel;
-use crate::side_effects::influx_download_log::LogEffect;
-use crate::side_effects::SideEffect;
+use crate::service::health_check::spawn_health_check;
+use crate::service::logging::init_logging;
+use crate::service::metrics::METRICS;
+use crate::service::protection::Protection;
+use crate::service::stats::Stats;
+use crate::service::webhook::Webhooks;
+use crate::side_effects::SideEffects;
 
 mod config_loader;
 mod vfs;
@@ -17,25 +35,111 @@ mod request_handler;
 struct State {
     captcha: Arc<dyn Verify>,
     wheel: Arc<DriveWheel>,
-    log: Arc<LogEffect>
+    side_effects: Arc<SideEffects>,
+    health: Arc<DriveHealth>,
+    mirror: Arc<MirrorSetting>,
+    /// SHA-256 of the admin token, compared in constant time
+    admin_token: Option<[u8; 32]>,
+    archive: Arc<ArchiveSetting>,
+    protection: Arc<Protection>,
+    auth: Arc<Auth>,
+    api_keys: Arc<ApiKeys>,
+    min_healthy_drives: usize,
+    stats: Arc<Stats>,
+    webhooks: Arc<Webhooks>,
+    trusted_proxies: Vec<IpAddr>,
+    /// where the api is served, for the links outside the site
+    public_url: Option<Url>,
+    /// fetches the files of the archives from the mirrors, shared so that the connections are reused
+    http_client: reqwest::Client,
 }
 
 #[actix_web::main]
 async fn main() {
-    let Config { influx, drives, cache, captcha } = load_config_file::load_config().unwrap();
-    let refresh_interval = cache.refresh_interval;
+    let Config {
+        sinks, event_queue, drives, cache, captcha, token_store, merge, mirror, health, admin, archive, visibility, protection, auth, api_keys, log, webhooks, trusted_proxies, public_url
+    } = load_config_file::load_config().unwrap();
+    // kept until exit, so that the log file is flushed
+    let _log_guard = init_logging(&log).expect("Invalid log config");
     let captcha = load_captcha(captcha);
-    let wheel = DriveWheel::new(drives, refresh_interval).await;
-    let log = Arc::new(LogEffect::new(influx));
+    let token_store = Arc::new(TokenStore::load(token_store));
+    let protection = Arc::new(Protection::new(protection).expect("Invalid protection config"));
+    let auth = Arc::new(Auth::new(auth).expect("Invalid auth config"));
+    let api_keys = Arc::new(ApiKeys::new(api_keys).expect("Invalid api key config"));
+    let webhooks = Arc::new(Webhooks::new(webhooks));
+    let wheel = DriveWheel::new(drives, cache, merge, visibility, protection.clone(), token_store, webhooks.clone());
+    let side_effects = Arc::new(SideEffects::new(sinks, event_queue).expect("Invalid sink config"));
+    let stats = Arc::new(Stats::new(side_effects.history()));
+    let health_check_interval = Duration::from_secs(health.interval);
+    let min_healthy_drives = health.min_healthy_drives;
+    let health = Arc::new(DriveHealth::new(Duration::from_secs(mirror.unhealthy_cooldown), webhooks.clone()));
+    spawn_health_check(wheel.clone(), health.clone(), health_check_interval);
     let state = Arc::new(State {
         captcha,
         wheel,
-        log
+        side_effects,
+        health,
+        mirror: Arc::new(mirror),
+        admin_token: admin.map(|admin| Sha256::digest(admin.token.as_bytes()).into()),
+        archive: Arc::new(archive),
+        protection,
+        auth,
+        api_keys,
+        min_healthy_drives,
+        stats,
+        webhooks,
+        trusted_proxies,
+        public_url,
+        http_client: reqwest::Client::new(),
     });
     HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(state.clone()))
+            // `Data::from` shares the `Arc`, so that handlers can extract `web::Data<State>`
+            .app_data(web::Data::from(state.clone()))
+            // every request is handled in a span with a request id, which is also sent back as `X-Request-Id`.
+            // the requests and their latency are counted by route pattern, so that paths in the tree do not become labels
+            .wrap_fn(|req, srv| {
+                let started = Instant::now();
+                let method = req.method().to_string();
+                let request_id = Uuid::new_v4().to_string();
+                let span = info_span!("request", id = %request_id, method = %method, path = %req.path());
+                let res = span.in_scope(|| srv.call(req));
+                async move {
+                    let mut res = res.await?;
+                    let elapsed = started.elapsed();
+                    let route = res.request().match_pattern().unwrap_or_else(|| "unmatched".to_owned());
+                    let status = res.status().as_u16().to_string();
+                    let labels = [route.as_str(), method.as_str(), status.as_str()];
+                    METRICS.requests.with_label_values(&labels).inc();
+                    METRICS.request_duration.with_label_values(&labels).observe(elapsed.as_secs_f64());
+                    debug!(status = %status, elapsed_ms = elapsed.as_millis() as u64, "Request finished");
+                    if let Ok(value) = HeaderValue::from_str(&request_id) {
+                        res.headers_mut().insert(HeaderName::from_static("x-request-id"), value);
+                    }
+                    Ok(res)
+                }.instrument(span)
+            })
             .service(request_handler::get_file_tree)
+            .service(request_handler::get_download_link::get_download_link)
+            .service(request_handler::metalink::get_metalink)
+            .service(request_handler::archive::get_archive)
+            .service(request_handler::unlock::unlock)
+            .service(request_handler::login::login)
+            .service(request_handler::login::logout)
+            .service(request_handler::login::get_me)
+            .service(request_handler::admin::get_drive_health)
+            .service(request_handler::admin::get_drives)
+            .service(request_handler::admin::get_snapshot)
+            .service(request_handler::admin::refresh_all)
+            .service(request_handler::admin::refresh_drive)
+            .service(request_handler::admin::enable_drive)
+            .service(request_handler::admin::disable_drive)
+            .service(request_handler::metrics::get_metrics)
+            .service(request_handler::probe::healthz)
+            .service(request_handler::probe::readyz)
+            .service(request_handler::stats::get_stats)
+            .service(request_handler::changes::get_changes)
+            .service(request_handler::changes::get_changes_feed)
     })
         .bind(("127.0.0.1", 8080)).expect("Can not bind to port 8080")
         .run()
@@ -0,0 +1,129 @@
+use actix_web::{get, HttpRequest, HttpResponse, post, web};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::service::drive_whell::WheelError;
+use crate::service::signer::digest_eq;
+use crate::State;
+
+/// Callers with this role can use the admin api, besides the admin token.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Whether the request carries the admin token, or is made by a user with the admin role.
+fn is_admin(req: &HttpRequest, state: &State) -> bool {
+    if state.auth.caller(req).roles.iter().any(|role| role == ADMIN_ROLE) {
+        return true;
+    }
+    let token = match &state.admin_token {
+        Some(token) => token,
+        None => return false,
+    };
+    match req.headers().get("Authorization").and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer ")) {
+        Some(value) => digest_eq(&Sha256::digest(value.as_bytes()).into(), token),
+        None => false,
+    }
+}
+
+#[derive(Serialize)]
+struct Generation {
+    generation: u64,
+}
+
+#[derive(Serialize)]
+struct AdminError {
+    error: String,
+}
+
+/// Respond with the new generation, or the error.
+fn generation_response(result: Result<u64, WheelError>) -> HttpResponse {
+    match result {
+        Ok(generation) => HttpResponse::Ok().json(Generation { generation }),
+        Err(error @ WheelError::UnknownDrive(_)) => HttpResponse::NotFound().json(AdminError { error: error.to_string() }),
+        Err(error @ WheelError::Rebuild(_)) => HttpResponse::InternalServerError().json(AdminError { error: error.to_string() }),
+    }
+}
+
+/// # Drive Health API
+/// Admin only. Shows whether each drive is healthy, and the last health check of it.
+#[get("/admin/health")]
+pub async fn get_drive_health(state: web::Data<State>, req: HttpRequest) -> HttpResponse {
+    if !is_admin(&req, &state) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    let report: Vec<_> = state.wheel.drives().iter()
+        .map(|drive| state.health.report(drive.name()))
+        .collect();
+    HttpResponse::Ok().json(report)
+}
+
+#[derive(Serialize)]
+struct DriveReport {
+    #[serde(flatten)]
+    status: crate::service::drive_whell::DriveStatus,
+    health: crate::service::drive_health::DriveHealthReport,
+}
+
+/// # Drive Status API
+/// Admin only. Shows the last refresh, errors, item count, token expiry and health of each drive.
+#[get("/admin/drives")]
+pub async fn get_drives(state: web::Data<State>, req: HttpRequest) -> HttpResponse {
+    if !is_admin(&req, &state) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    let report: Vec<_> = state.wheel.status().await.into_iter()
+        .map(|status| {
+            let health = state.health.report(&status.drive);
+            DriveReport { status, health }
+        })
+        .collect();
+    HttpResponse::Ok().json(report)
+}
+
+/// # Snapshot API
+/// Admin only. Shows the generation of the current tree, which is increased every time the tree is rebuilt.
+#[get("/admin/snapshot")]
+pub async fn get_snapshot(state: web::Data<State>, req: HttpRequest) -> HttpResponse {
+    if !is_admin(&req, &state) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    HttpResponse::Ok().json(Generation { generation: state.wheel.generation() })
+}
+
+/// # Refresh API
+/// Admin only. Load all the enabled drives again right now, and respond with the new generation.
+#[post("/admin/refresh")]
+pub async fn refresh_all(state: web::Data<State>, req: HttpRequest) -> HttpResponse {
+    if !is_admin(&req, &state) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    generation_response(state.wheel.refresh_all().await)
+}
+
+/// # Refresh Drive API
+/// Admin only. Load one drive again right now, and respond with the new generation.
+#[post("/admin/drives/{name}/refresh")]
+pub async fn refresh_drive(state: web::Data<State>, name: web::Path<(String,)>, req: HttpRequest) -> HttpResponse {
+    if !is_admin(&req, &state) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    generation_response(state.wheel.refresh_drive(&name.0).await)
+}
+
+/// # Enable Drive API
+/// Admin only. Put a disabled drive back into the tree.
+#[post("/admin/drives/{name}/enable")]
+pub async fn enable_drive(state: web::Data<State>, name: web::Path<(String,)>, req: HttpRequest) -> HttpResponse {
+    if !is_admin(&req, &state) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    generation_response(state.wheel.set_enabled(&name.0, true).await)
+}
+
+/// # Disable Drive API
+/// Admin only. Remove a drive from the tree until it is enabled again, the change is lost on restart.
+#[post("/admin/drives/{name}/disable")]
+pub async fn disable_drive(state: web::Data<State>, name: web::Path<(String,)>, req: HttpRequest) -> HttpResponse {
+    if !is_admin(&req, &state) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    generation_response(state.wheel.set_enabled(&name.0, false).await)
+}
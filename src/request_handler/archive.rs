@@ -0,0 +1,210 @@
+use std::io;
+use actix_web::{Error, get, HttpRequest, HttpResponse, web};
+use actix_web::web::{Bytes, Query};
+use futures::StreamExt;
+use reqwest::StatusCode;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::warn;
+use crate::request_handler::get_download_link::CaptchaQuery;
+use crate::service::zip_stream::{check_name, ZipStreamWriter};
+use crate::request_handler::{authorize, can_read, finish_download, record_download};
+use crate::side_effects::DownloadKind;
+use crate::State;
+use crate::vfs::combine::{CombinableVfsFile, DownloadLink};
+use crate::vfs::path_compress::TryPathResult::{*};
+use crate::vfs::select::MirrorRequest;
+use crate::vfs::VfsBasicMeta;
+
+/// How many chunks can be buffered before the client reads them.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// # Get Directory Archive API
+/// Downloads a whole directory as a ZIP archive, which is streamed from the mirrors of each file without buffering to disk.
+/// The root is not a directory in the index, so it can not be archived as a whole.
+/// Just like downloading, user must provide `?token=xxx` as captcha, or an API key.
+/// Directories larger than `archive.max_size` are rejected with `413 Payload Too Large`,
+/// and the ones with a path too long for ZIP with `400 Bad Request`, before anything is sent.
+/// The download is recorded once the archive ends, as `200` if it is complete, `499` if the client is gone,
+/// or `502` if a file could not be fetched from any mirror.
+#[get("/api/archive/{path:.*}")]
+pub async fn get_archive(
+    state: web::Data<State>,
+    path: web::Path<(String,)>,
+    query: Query<CaptchaQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    // paths in `IndexedVfs` start with `/`
+    let path = format!("/{}", path.0);
+    let (caller, mut props) = match authorize(&state, &req, &query.token, &path, DownloadKind::Archive).await {
+        Ok(authorized) => authorized,
+        Err(response) => return Ok(response),
+    };
+    // the entries are named relative to the parent of the directory
+    let parent = path.rsplit_once('/').map(|(parent, _)| parent.to_owned()).unwrap_or_default();
+    let (name, files) = match state.wheel.get_path_map().try_path(&path) {
+        NotFound => return Ok(finish_download(&state, props, HttpResponse::NotFound().finish())),
+        File(_) => return Ok(finish_download(&state, props, HttpResponse::NotAcceptable().finish())),
+        Dir(dir) => {
+            // the ones the caller can not read are skipped
+            let include = |entry_path: &str| can_read(&state, &caller, &format!("{}/{}", parent, entry_path), &req);
+            (dir.name().to_owned(), dir.walk_files(dir.name(), &include))
+        }
+    };
+    let total_size: u64 = files.iter().map(|(_, file)| file.size()).sum();
+    props.file_size = Some(total_size);
+    if total_size > state.archive.max_size {
+        return Ok(finish_download(&state, props, HttpResponse::PayloadTooLarge().finish()));
+    }
+    if let Some(Err(e)) = files.iter().map(|(name, _)| check_name(entry_name(name))).find(Result::is_err) {
+        return Ok(finish_download(&state, props, HttpResponse::BadRequest().body(e)));
+    }
+    let ip = props.request_ip.clone();
+
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+    let archive_state = state.clone().into_inner();
+    tokio::spawn(async move {
+        props.status = match write_archive(&archive_state, &parent, &ip, files, &sender).await {
+            Ok(()) => 200,
+            Err(err) => {
+                warn!("Archive of {} is aborted: {}", path, err);
+                let status = if err.kind() == io::ErrorKind::BrokenPipe { 499 } else { 502 };
+                // the client will see a broken archive instead of a complete one
+                let _ = sender.send(Err(err)).await;
+                status
+            }
+        };
+        record_download(&archive_state, props);
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .append_header(("Content-Disposition", format!("attachment; filename=\"{}.zip\"", name.replace('"', ""))))
+        .streaming(ReceiverStream::new(receiver)))
+}
+
+type ChunkSender = mpsc::Sender<Result<Bytes, io::Error>>;
+
+/// Fetch the files one by one from the selected mirrors, and send them through `sender` as a ZIP archive.
+/// The names of `files` are relative to `parent`.
+async fn write_archive(
+    state: &State,
+    parent: &str,
+    ip: &str,
+    files: Vec<(String, CombinableVfsFile)>,
+    sender: &ChunkSender,
+) -> Result<(), io::Error> {
+    let mut writer = ZipStreamWriter::new();
+    for (name, file) in files {
+        let request = MirrorRequest {
+            strategy: state.mirror.strategy_for(&format!("{}/{}", parent, name)),
+            client_ip: ip,
+            health: &state.health,
+        };
+        // the names are checked before the response is sent
+        let header = writer.start_entry(entry_name(&name), file.last_modified())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        send(sender, header).await?;
+        let mut hasher = crc32fast::Hasher::new();
+        let size = write_content(state, &name, &file, &request, &mut hasher, sender).await?;
+        if size != file.size() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} is {} bytes, expected {}", name, size, file.size())));
+        }
+        send(sender, writer.finish_entry(hasher.finalize(), size)).await?;
+    }
+    send(sender, writer.finish()).await
+}
+
+/// The name of the entry in the archive, which is relative.
+fn entry_name(name: &str) -> &str {
+    name.trim_start_matches('/')
+}
+
+/// Why a file stopped being fetched.
+enum Interrupted {
+    /// the mirror failed, the next one can continue. `drive_failed` for transport errors and 5xx,
+    /// the others like a `404` of a stale file say nothing about the health of the drive
+    Mirror { error: String, drive_failed: bool },
+    /// the client is gone
+    Client(io::Error),
+}
+
+/// Send the content of the file, trying the selected mirror first, then the others.
+/// The drive of a mirror which fails by itself is marked unhealthy, and the next mirror continues from where it stopped by a range request.
+/// Gives the size sent.
+async fn write_content(
+    state: &State,
+    name: &str,
+    file: &CombinableVfsFile,
+    request: &MirrorRequest<'_>,
+    hasher: &mut crc32fast::Hasher,
+    sender: &ChunkSender,
+) -> Result<u64, io::Error> {
+    let mirrors = file.mirrors();
+    let selected = mirrors.select(request);
+    let available = mirrors.available_links(&state.health);
+    // the selected one, then the healthy ones, then the others
+    let mut links: Vec<&DownloadLink> = selected.into_iter().collect();
+    for link in available.into_iter().chain(mirrors.links()) {
+        if !links.iter().any(|tried| std::ptr::eq(*tried, link)) {
+            links.push(link);
+        }
+    }
+    let mut size = 0u64;
+    let mut last_error = format!("No mirror of {}", name);
+    for link in links {
+        let url = link.url(&state.health).await;
+        let result = match fetch_from(&state.http_client, &url, hasher, &mut size, sender).await {
+            // a body ended early is continued by the next mirror too
+            Ok(()) if size < file.size() => Err(Interrupted::Mirror { error: format!("Ended at {} bytes", size), drive_failed: true }),
+            result => result,
+        };
+        match result {
+            Ok(()) => return Ok(size),
+            Err(Interrupted::Client(err)) => return Err(err),
+            Err(Interrupted::Mirror { error, drive_failed }) => {
+                warn!("Failed to fetch {} from {}, try the next mirror: {}", name, link.drive().name, error);
+                if drive_failed {
+                    state.health.mark_failed(&link.drive().name, error.clone());
+                }
+                last_error = format!("Failed to fetch {}: {}", name, error);
+            }
+        }
+    }
+    Err(io::Error::other(last_error))
+}
+
+/// Send the content from `url`, starting at `size` bytes, which is increased as the chunks are sent.
+async fn fetch_from(
+    client: &reqwest::Client,
+    url: &str,
+    hasher: &mut crc32fast::Hasher,
+    size: &mut u64,
+    sender: &ChunkSender,
+) -> Result<(), Interrupted> {
+    let mut request = client.get(url);
+    if *size > 0 {
+        request = request.header("Range", format!("bytes={}-", size));
+    }
+    let transport = |err: reqwest::Error| Interrupted::Mirror { error: err.to_string(), drive_failed: true };
+    let response = request.send().await.map_err(transport)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Interrupted::Mirror { error: status.to_string(), drive_failed: status.is_server_error() });
+    }
+    if *size > 0 && status != StatusCode::PARTIAL_CONTENT {
+        return Err(Interrupted::Mirror { error: "Range request is not supported".to_owned(), drive_failed: false });
+    }
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(transport)?;
+        hasher.update(&chunk);
+        *size += chunk.len() as u64;
+        send(sender, chunk).await.map_err(Interrupted::Client)?;
+    }
+    Ok(())
+}
+
+async fn send(sender: &ChunkSender, chunk: impl Into<Bytes>) -> Result<(), io::Error> {
+    sender.send(Ok(chunk.into())).await
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Client disconnected"))
+}
@@ -0,0 +1,142 @@
+use std::time::{Duration, UNIX_EPOCH};
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use actix_web::web::Query;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use crate::config_loader::config_struct::Permission;
+use crate::request_handler::escape_xml;
+use crate::service::drive_whell::TreeChange;
+use crate::State;
+
+/// How many new files are in the feed at most.
+const FEED_ENTRIES: usize = 50;
+
+#[derive(Deserialize)]
+pub struct ChangesQuery {
+    /// the generation of the tree which the caller has, 0 for none
+    #[serde(default)]
+    pub since: u64,
+}
+
+#[derive(Serialize)]
+struct ChangesResponse {
+    /// the generation of the current tree, to be the next `since`
+    generation: u64,
+    /// false if some changes after `since` are forgotten, the whole tree should be fetched again
+    complete: bool,
+    /// the oldest first
+    changes: Vec<TreeChange>,
+}
+
+/// The changes after `since` with only the paths which the caller can list, the oldest first.
+/// The files in protected directories are only shown after all of them on the way are unlocked, just like the tree.
+fn visible_changes(state: &State, req: &HttpRequest, since: u64) -> (Vec<TreeChange>, bool) {
+    let caller = state.auth.caller(req);
+    let can_list = |path: &str| {
+        state.auth.permission(&caller, path) >= Permission::List && state.protection.is_unlocked(path, req)
+    };
+    let (changes, complete) = state.wheel.changes_since(since);
+    let changes = changes.iter()
+        .map(|change| TreeChange {
+            generation: change.generation,
+            timestamp: change.timestamp,
+            diff: change.diff.filter(&can_list),
+        })
+        .filter(|change| !change.diff.is_empty())
+        .collect();
+    (changes, complete)
+}
+
+/// # Changes API
+/// The files added, removed and modified by the refreshes after the tree of generation `?since=`.
+/// Only the latest changes are kept, `complete` is false when some of them are forgotten.
+#[get("/api/changes")]
+pub async fn get_changes(state: web::Data<State>, query: Query<ChangesQuery>, req: HttpRequest) -> HttpResponse {
+    let generation = state.wheel.generation();
+    let (changes, complete) = visible_changes(&state, &req, query.since);
+    HttpResponse::Ok().json(ChangesResponse {
+        generation,
+        complete,
+        changes,
+    })
+}
+
+/// # New Files Feed
+/// An Atom feed of the files added by the latest refreshes, the newest first, linked to their download api.
+/// The links are made from `public_url`, not from the `Host` of the request which the client can choose,
+/// so the feed is `404 Not Found` when `public_url` is not configured.
+#[get("/api/changes/feed")]
+pub async fn get_changes_feed(state: web::Data<State>, req: HttpRequest) -> HttpResponse {
+    let Some(base) = &state.public_url else {
+        return HttpResponse::NotFound().finish();
+    };
+    let (changes, _) = visible_changes(&state, &req, 0);
+    HttpResponse::Ok()
+        .content_type("application/atom+xml")
+        .body(write_feed(base, &changes))
+}
+
+fn to_rfc3339(timestamp: u128) -> String {
+    DateTime::<Utc>::from(UNIX_EPOCH + Duration::from_millis(timestamp as u64)).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// The url of `/api/download{path}`, with the path percent encoded.
+fn download_url(base: &Url, path: &str) -> String {
+    let mut url = base.clone();
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.pop_if_empty().extend(["api", "download"]).extend(path.split('/').skip(1));
+    }
+    url.to_string()
+}
+
+fn write_feed(base: &Url, changes: &[TreeChange]) -> String {
+    let feed_url = format!("{}api/changes/feed", base);
+    let updated = changes.last().map(|change| change.timestamp).unwrap_or(0);
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("  <title>New files</title>\n");
+    xml.push_str(&format!("  <id>{}</id>\n", escape_xml(&feed_url)));
+    xml.push_str(&format!("  <link rel=\"self\" href=\"{}\"/>\n", escape_xml(&feed_url)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", to_rfc3339(updated)));
+    xml.push_str("  <author><name>rlist</name></author>\n");
+    let added = changes.iter().rev()
+        .flat_map(|change| change.diff.added.iter().map(move |file| (change, file)))
+        .take(FEED_ENTRIES);
+    for (change, file) in added {
+        let url = download_url(base, &file.path);
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", escape_xml(&file.path)));
+        // a file added again later is a new entry
+        xml.push_str(&format!("    <id>{}#{}</id>\n", escape_xml(&url), change.generation));
+        xml.push_str(&format!("    <link href=\"{}\"/>\n", escape_xml(&url)));
+        xml.push_str(&format!("    <updated>{}</updated>\n", to_rfc3339(change.timestamp)));
+        xml.push_str(&format!("    <summary>{} bytes</summary>\n", file.size));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::diff::{ChangedFile, TreeDiff};
+
+    #[test]
+    fn test_changes_feed() {
+        let base = Url::parse("https://example.com/").unwrap();
+        assert_eq!(download_url(&base, "/a b/c&d.zip"), "https://example.com/api/download/a%20b/c&d.zip");
+        let change = |generation: u64, path: &str| TreeChange {
+            generation,
+            timestamp: 1_700_000_000_000,
+            diff: TreeDiff {
+                added: vec![ChangedFile { path: path.to_owned(), size: 1, last_modified: 0 }],
+                ..TreeDiff::default()
+            },
+        };
+        let feed = write_feed(&base, &[change(2, "/old"), change(3, "/new")]);
+        assert!(feed.find("/new").unwrap() < feed.find("/old").unwrap());
+        assert!(feed.contains("<id>https://example.com/api/download/new#3</id>"));
+        assert!(feed.contains("<updated>2023-11-14T22:13:20Z</updated>"));
+    }
+}
@@ -1,10 +1,22 @@
-use actix_web::{get, web};
+use actix_web::{get, HttpRequest, web};
+use crate::config_loader::config_struct::Permission;
+use crate::service::stats::WINDOWS;
 use crate::State;
 
 /// # Get File Tree API
 /// Users can only get the file tree **without** download links.
+/// Only the entries which the caller can list are shown.
+/// Protected directories are `locked` without children, until they are unlocked by `/api/unlock`.
+/// When there is a local download log, files carry their `downloads` in the last 30 days.
 #[get("/api/file_tree")]
-async fn get_file_tree(state: web::Data<State>) -> String {
+async fn get_file_tree(state: web::Data<State>, req: HttpRequest) -> String {
     let dir = state.wheel.get_hidden_url();
-    serde_json::to_string(dir.as_ref()).unwrap()
+    let caller = state.auth.caller(&req);
+    let can_list = |path: &str| state.auth.permission(&caller, path) >= Permission::List;
+    let unlocked = |path: &str| state.protection.is_unlocked(path, &req);
+    // the last report computed in background, so the tree never waits for the download log
+    let report = state.stats.report();
+    let downloads = |path: &str| report.as_ref().map(|report| report.downloads(WINDOWS.len() - 1, path));
+    let tree = serde_json::to_string(&dir.view(&can_list, &unlocked, &downloads)).unwrap();
+    tree
 }
\ No newline at end of file
@@ -1,18 +1,24 @@
 use actix_web::{Error, get, HttpRequest, HttpResponse, web};
 use actix_web::web::Query;
-use crate::side_effects::{SideEffect, SideEffectProps};
+use crate::request_handler::{authorize, finish_download};
+use crate::service::metrics::METRICS;
+use crate::side_effects::DownloadKind;
 use crate::State;
 use crate::vfs::path_compress::TryPathResult::{*};
-use crate::vfs::VfsFile;
+use crate::vfs::select::MirrorRequest;
+use crate::vfs::VfsBasicMeta;
 
 #[derive(serde::Deserialize)]
 pub struct CaptchaQuery {
+    /// not needed with an API key
+    #[serde(default)]
     pub token: String
 }
 
 /// # Get Download Link API
 /// User must provide a valid token(as captcha) to get the download link.
 /// If captcha is enabled, user must provide `?token=xxx` to get the download link.
+/// Automated clients can send `Authorization: Bearer {key}` with an API key instead.
 #[get("/api/download/{path:.*}")]
 pub async fn get_download_link(
     state: web::Data<State>,
@@ -20,42 +26,42 @@ pub async fn get_download_link(
     query: Query<CaptchaQuery>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
-    let token = query.token.as_str();
-    let path = path.0.as_str();
-    let log_effect = state.log.clone();
-    let connection_info = req.connection_info();
-    let ip = match connection_info.realip_remote_addr() {
-        None => {
-            return Ok(HttpResponse::BadRequest().finish());
-        }
-        Some(ip) => { ip }
-    };
-    let ua = match req.headers().get("User-Agent") {
-        None => "",
-        Some(ua) => ua.to_str().unwrap_or("")
+    // paths in `IndexedVfs` start with `/`
+    let path = format!("/{}", path.0);
+    let path = path.as_str();
+    let (_, mut props) = match authorize(&state, &req, &query.token, path, DownloadKind::File).await {
+        Ok(authorized) => authorized,
+        Err(response) => return Ok(response),
     };
-    log_effect.do_effect(SideEffectProps {
-        request_ip: ip.to_string(),
-        user_agent: ua.to_string(),
-        file_name: path.to_string()
-    }).await;
-    let verify = state.captcha.clone();
-    if !verify.verify(token, ip).await {
-        return Ok(HttpResponse::Unauthorized().finish());
-    }
     let wheel = state.wheel.clone();
     let path_map = wheel.get_path_map();
     let file = path_map.try_path(path);
-    match file {
+    let response = match file {
         NotFound => {
-            Ok(HttpResponse::NotFound().finish())
+            HttpResponse::NotFound().finish()
         },
         File(file) => {
-            let url = file.on_download();
-            Ok(HttpResponse::TemporaryRedirect().append_header(("Location", url)).finish())
+            props.file_size = Some(file.size());
+            let request = MirrorRequest {
+                strategy: state.mirror.strategy_for(path),
+                client_ip: &props.request_ip,
+                health: &state.health,
+            };
+            match file.mirrors().select(&request) {
+                Some(link) => {
+                    METRICS.downloads.with_label_values(&[&link.drive().name]).inc();
+                    let url = link.url(&state.health).await;
+                    props.drive = Some(link.drive().name.clone());
+                    props.mirror = reqwest::Url::parse(&url).ok()
+                        .and_then(|url| url.host_str().map(|host| host.to_owned()));
+                    HttpResponse::TemporaryRedirect().append_header(("Location", url)).finish()
+                }
+                None => HttpResponse::NotFound().finish(),
+            }
         },
         Dir(_) => {
-            Ok(HttpResponse::NotAcceptable().finish())
+            HttpResponse::NotAcceptable().finish()
         }
-    }
+    };
+    Ok(finish_download(&state, props, response))
 }
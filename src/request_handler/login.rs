@@ -0,0 +1,62 @@
+use actix_web::{get, HttpRequest, HttpResponse, post, web};
+use actix_web::cookie::Cookie;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use crate::request_handler::client_ip;
+use crate::service::auth::{LoginRejection, SESSION_COOKIE};
+use crate::State;
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    pub name: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    pub token: String,
+    /// in seconds
+    pub expires_in: u64,
+}
+
+/// # Login API
+/// Log in with `{"name": "alice", "password": "xxx"}`.
+/// The session token is set as a cookie, and also given in the response to be sent as `Authorization: Bearer {token}`.
+/// Gives `401 Unauthorized` if the name or the password is wrong,
+/// and `429 Too Many Requests` after too many wrong passwords of the user from the same ip, or from all the ips together.
+#[post("/api/login")]
+pub async fn login(state: web::Data<State>, body: web::Json<LoginRequest>, req: HttpRequest) -> HttpResponse {
+    let Some(ip) = client_ip(&req, &state.trusted_proxies) else {
+        return HttpResponse::BadRequest().finish();
+    };
+    match state.auth.login(&body.name, &body.password, ip) {
+        Ok(token) => HttpResponse::Ok()
+            .cookie(state.auth.session_cookie(token.clone()))
+            .json(LoginResponse {
+                token,
+                expires_in: state.auth.session_max_age().as_secs(),
+            }),
+        Err(LoginRejection::WrongPassword) => {
+            warn!("Failed login of {} from {}", body.name, ip);
+            HttpResponse::Unauthorized().finish()
+        }
+        Err(LoginRejection::RateLimited) => HttpResponse::TooManyRequests().finish(),
+    }
+}
+
+/// # Current User API
+/// Shows the name and roles of the caller, the name is `null` if not logged in.
+#[get("/api/me")]
+pub async fn get_me(state: web::Data<State>, req: HttpRequest) -> HttpResponse {
+    HttpResponse::Ok().json(state.auth.caller(&req))
+}
+
+/// # Logout API
+/// Remove the session cookie. The token itself stays valid until it expires.
+#[post("/api/logout")]
+pub async fn logout() -> HttpResponse {
+    let mut cookie = Cookie::new(SESSION_COOKIE, "");
+    cookie.set_path("/");
+    cookie.make_removal();
+    HttpResponse::NoContent().cookie(cookie).finish()
+}
@@ -0,0 +1,160 @@
+use actix_web::{Error, get, HttpRequest, HttpResponse, web};
+use actix_web::web::Query;
+use futures::StreamExt;
+use serde::Deserialize;
+use crate::service::drive_health::DriveHealth;
+use crate::request_handler::{authorize, can_read, escape_xml, finish_download};
+use crate::side_effects::DownloadKind;
+use crate::State;
+use crate::vfs::combine::CombinableVfsFile;
+use crate::vfs::path_compress::TryPathResult::{*};
+use crate::vfs::VfsBasicMeta;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+pub enum DescriptorFormat {
+    /// Metalink v4, RFC 5854
+    #[default]
+    #[serde(rename = "metalink")]
+    Metalink,
+    /// aria2 input file, for `aria2c -i`
+    #[serde(rename = "aria2")]
+    Aria2,
+}
+
+#[derive(Deserialize)]
+pub struct MetalinkQuery {
+    #[serde(default)]
+    pub token: String,
+    #[serde(default)]
+    pub format: DescriptorFormat,
+}
+
+/// A file with all its mirrors, ready to be written into a descriptor.
+struct DescribedFile {
+    /// relative to the requested path, like `dir/file`
+    path: String,
+    size: u64,
+    sha1: Option<String>,
+    /// sorted by the priority of their drives, the preferred first
+    urls: Vec<String>,
+}
+
+/// Files of a directory described at the same time, so that a large directory does not flood the drives with requests.
+const CONCURRENT_FILES: usize = 4;
+/// Urls of a file resolved at the same time.
+const CONCURRENT_MIRRORS: usize = 2;
+
+/// # Get Multi-source Descriptor API
+/// Lists all the mirrors of a file, or of all the files in a directory, so that download tools can download from them in parallel.
+/// Just like downloading, user must provide `?token=xxx` as captcha, or an API key. `?format=aria2` gives an aria2 input file instead of metalink.
+#[get("/api/metalink/{path:.*}")]
+pub async fn get_metalink(
+    state: web::Data<State>,
+    path: web::Path<(String,)>,
+    query: Query<MetalinkQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    // paths in `IndexedVfs` start with `/`
+    let path = format!("/{}", path.0);
+    let (caller, mut props) = match authorize(&state, &req, &query.token, &path, DownloadKind::Metalink).await {
+        Ok(authorized) => authorized,
+        Err(response) => return Ok(response),
+    };
+    let files = match state.wheel.get_path_map().try_path(&path) {
+        NotFound => return Ok(finish_download(&state, props, HttpResponse::NotFound().finish())),
+        File(file) => vec![(file.name().to_owned(), file)],
+        Dir(dir) => {
+            // the entries are named relative to the parent of the directory, the ones the caller can not read are skipped
+            let parent = path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
+            let include = |entry_path: &str| can_read(&state, &caller, &format!("{}/{}", parent, entry_path), &req);
+            dir.walk_files(dir.name(), &include)
+        }
+    };
+    props.file_size = Some(files.iter().map(|(_, file)| file.size()).sum());
+    let files: Vec<DescribedFile> = futures::stream::iter(files.into_iter().map(|(path, file)| {
+        describe_file(path, file, &state.health)
+    })).buffered(CONCURRENT_FILES).collect().await;
+    let response = match query.format {
+        DescriptorFormat::Metalink => HttpResponse::Ok()
+            .content_type("application/metalink4+xml")
+            .body(write_metalink(&files)),
+        DescriptorFormat::Aria2 => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(write_aria2(&files)),
+    };
+    Ok(finish_download(&state, props, response))
+}
+
+/// Get fresh urls of all the available mirrors of the file.
+async fn describe_file(path: String, file: CombinableVfsFile, health: &DriveHealth) -> DescribedFile {
+    let mut links = file.mirrors().available_links(health);
+    links.sort_by_key(|link| std::cmp::Reverse(link.drive().priority));
+    let urls = futures::stream::iter(links.into_iter().map(|link| link.url(health)))
+        .buffered(CONCURRENT_MIRRORS)
+        .collect()
+        .await;
+    DescribedFile {
+        path,
+        size: file.size(),
+        sha1: file.hash().sha1.clone(),
+        urls,
+    }
+}
+
+fn write_metalink(files: &[DescribedFile]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<metalink xmlns=\"urn:ietf:params:xml:ns:metalink\">\n");
+    for file in files {
+        xml.push_str(&format!("  <file name=\"{}\">\n", escape_xml(&file.path)));
+        xml.push_str(&format!("    <size>{}</size>\n", file.size));
+        if let Some(sha1) = &file.sha1 {
+            xml.push_str(&format!("    <hash type=\"sha-1\">{}</hash>\n", escape_xml(sha1)));
+        }
+        // priority 1 is the most preferred
+        for (index, url) in file.urls.iter().enumerate() {
+            xml.push_str(&format!("    <url priority=\"{}\">{}</url>\n", index + 1, escape_xml(url)));
+        }
+        xml.push_str("  </file>\n");
+    }
+    xml.push_str("</metalink>\n");
+    xml
+}
+
+/// Each file is a line of tab separated urls, followed by indented options.
+/// The files with a line break or a tab in the path or a url are left out, as they would break the lines into other options.
+fn write_aria2(files: &[DescribedFile]) -> String {
+    let breaks_line = |value: &str| value.contains(['\r', '\n', '\t']);
+    let mut input = String::new();
+    for file in files {
+        if breaks_line(&file.path) || file.urls.iter().any(|url| breaks_line(url)) {
+            continue;
+        }
+        input.push_str(&file.urls.join("\t"));
+        input.push('\n');
+        input.push_str(&format!("  out={}\n", file.path));
+        if let Some(sha1) = &file.sha1 {
+            input.push_str(&format!("  checksum=sha-1={}\n", sha1));
+        }
+    }
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aria2_leaves_out_line_breaks() {
+        let file = |path: &str, url: &str| DescribedFile {
+            path: path.to_owned(),
+            size: 1,
+            sha1: None,
+            urls: vec![url.to_owned(), "https://b/x".to_owned()],
+        };
+        let files = [
+            file("dir/a", "https://a/x"),
+            file("b\n  dir=/etc", "https://a/y"),
+            file("c", "https://a/z\r\n  out=/etc/passwd"),
+        ];
+        assert_eq!(write_aria2(&files), "https://a/x\thttps://b/x\n  out=dir/a\n");
+    }
+}
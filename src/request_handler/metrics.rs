@@ -0,0 +1,11 @@
+use actix_web::{get, HttpResponse};
+use crate::service::metrics::METRICS;
+
+/// # Metrics API
+/// All the metrics in Prometheus text format, for scraping.
+#[get("/metrics")]
+pub async fn get_metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(METRICS.render())
+}
@@ -1,4 +1,147 @@
+use std::net::IpAddr;
+use std::time::SystemTime;
+use actix_web::{HttpRequest, HttpResponse};
+use crate::config_loader::config_struct::Permission;
+use crate::service::api_key::KeyRejection;
+use crate::service::auth::Caller;
+use crate::service::webhook::WebhookEvent;
+use crate::side_effects::{DownloadKind, SideEffectProps};
+use crate::State;
+
 mod file_tree;
+pub mod admin;
+pub mod archive;
+pub mod changes;
+pub mod metalink;
 pub mod get_download_link;
+pub mod login;
+pub mod metrics;
+pub mod probe;
+pub mod stats;
+pub mod unlock;
+
+pub use file_tree::get_file_tree;
+
+/// # Authorize Download
+/// Check a request to download `kind` from `path`, the request is recorded as long as it has an ip.
+/// A request with a valid API key passes without captcha, others must provide a valid captcha `token`.
+/// Then the caller must be able to read `path`.
+/// Gives the caller and the download event to be completed and emitted by `finish_download`,
+/// or the response to refuse the request, which is emitted already.
+async fn authorize(state: &State, req: &HttpRequest, token: &str, path: &str, kind: DownloadKind) -> Result<(Caller, SideEffectProps), HttpResponse> {
+    let ip = match req.connection_info().realip_remote_addr() {
+        None => return Err(HttpResponse::BadRequest().finish()),
+        Some(ip) => ip.to_string(),
+    };
+    let header = |name: &str| req.headers().get(name).and_then(|value| value.to_str().ok()).map(|value| value.to_owned());
+    let key = state.api_keys.authenticate(req, path);
+    let props = SideEffectProps {
+        request_ip: ip,
+        user_agent: header("User-Agent").unwrap_or_default(),
+        file_name: path.to_string(),
+        kind,
+        api_key: key.as_ref().ok().and_then(|key| key.as_ref()).map(|key| key.name.clone()),
+        drive: None,
+        mirror: None,
+        file_size: None,
+        status: 0,
+        referer: header("Referer"),
+        timestamp: SystemTime::now(),
+    };
+    let caller = match key {
+        Err(KeyRejection::Expired) => return Err(finish_download(state, props, HttpResponse::Unauthorized().finish())),
+        Err(KeyRejection::OutOfScope) => return Err(finish_download(state, props, HttpResponse::Forbidden().finish())),
+        Err(KeyRejection::RateLimited) => return Err(finish_download(state, props, HttpResponse::TooManyRequests().finish())),
+        Ok(Some(key)) => Caller {
+            name: Some(key.name),
+            roles: key.roles,
+        },
+        Ok(None) => {
+            if !state.captcha.verify(token, &props.request_ip).await {
+                return Err(finish_download(state, props, HttpResponse::Unauthorized().finish()));
+            }
+            state.auth.caller(req)
+        }
+    };
+    if !can_read(state, &caller, path, req) {
+        return Err(finish_download(state, props, HttpResponse::Forbidden().finish()));
+    }
+    Ok((caller, props))
+}
+
+/// Emit the download event with the status of the response, and pass the response through.
+fn finish_download(state: &State, mut props: SideEffectProps, response: HttpResponse) -> HttpResponse {
+    props.status = response.status().as_u16();
+    record_download(state, props);
+    response
+}
+
+/// Emit the download event with its `status`, successful downloads are also posted to the webhooks.
+/// For the responses which are streamed, it is called once the stream ends.
+fn record_download(state: &State, props: SideEffectProps) {
+    if (200..400).contains(&props.status) {
+        state.webhooks.notify(WebhookEvent::Download {
+            path: props.file_name.clone(),
+            kind: props.kind,
+            ip: props.request_ip.clone(),
+            drive: props.drive.clone(),
+            file_size: props.file_size,
+            status: props.status,
+        });
+    }
+    state.side_effects.emit(props);
+}
+
+/// Whether the caller can download `path`, which needs the read permission and all the protected directories on the way unlocked.
+fn can_read(state: &State, caller: &Caller, path: &str, req: &HttpRequest) -> bool {
+    state.auth.permission(caller, path) >= Permission::Read && state.protection.is_unlocked(path, req)
+}
+
+/// The ip of the client, which is used to limit wrong guesses and so can not be forged by headers.
+/// It is the peer of the connection, unless the peer is one of `trusted_proxies`,
+/// then `X-Forwarded-For` is followed from the end, through the trusted proxies, to the first address which is not one.
+fn client_ip(req: &HttpRequest, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let mut ip = req.peer_addr()?.ip();
+    if !trusted_proxies.contains(&ip) {
+        return Some(ip);
+    }
+    let forwarded: Vec<&str> = req.headers().get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    for hop in forwarded.into_iter().rev() {
+        let Ok(hop) = hop.trim().parse::<IpAddr>() else { break };
+        ip = hop;
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+    }
+    Some(ip)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use super::*;
 
-pub use file_tree::get_file_tree;
\ No newline at end of file
+    #[test]
+    fn test_client_ip() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let request = |peer: &str| TestRequest::default()
+            .peer_addr(format!("{}:1234", peer).parse().unwrap())
+            .insert_header(("X-Forwarded-For", "1.1.1.1, 2.2.2.2, 10.0.0.1"))
+            .to_http_request();
+        // forwarded addresses are only believed from a trusted proxy
+        assert_eq!(client_ip(&request("3.3.3.3"), &[proxy]), Some("3.3.3.3".parse().unwrap()));
+        // the client can prepend anything, only the address added by the proxy is taken
+        assert_eq!(client_ip(&request("10.0.0.1"), &[proxy]), Some("2.2.2.2".parse().unwrap()));
+    }
+}
@@ -0,0 +1,56 @@
+use actix_web::{get, HttpResponse, web};
+use serde::Serialize;
+use crate::service::drive_health::DriveHealthReport;
+use crate::State;
+
+/// # Liveness Probe
+/// The process is alive and serving requests.
+#[get("/healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().body("ok")
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    /// the generation of the current tree, 0 before the first one is loaded
+    generation: u64,
+    healthy_drives: usize,
+    min_healthy_drives: usize,
+    drives: Vec<DriveReadiness>,
+}
+
+#[derive(Serialize)]
+struct DriveReadiness {
+    #[serde(flatten)]
+    health: DriveHealthReport,
+    enabled: bool,
+}
+
+/// # Readiness Probe
+/// Ready when the tree is loaded and at least `min_healthy_drives` enabled drives are healthy,
+/// otherwise `503 Service Unavailable`. The body shows the health of each drive either way.
+#[get("/readyz")]
+pub async fn readyz(state: web::Data<State>) -> HttpResponse {
+    let enabled: Vec<&str> = state.wheel.enabled_drives().iter().map(|drive| drive.name()).collect();
+    let drives: Vec<DriveReadiness> = state.wheel.drives().iter()
+        .map(|drive| DriveReadiness {
+            health: state.health.report(drive.name()),
+            enabled: enabled.contains(&drive.name()),
+        })
+        .collect();
+    let healthy_drives = drives.iter().filter(|drive| drive.enabled && drive.health.healthy).count();
+    let generation = state.wheel.generation();
+    let readiness = Readiness {
+        ready: generation > 0 && healthy_drives >= state.min_healthy_drives,
+        generation,
+        healthy_drives,
+        min_healthy_drives: state.min_healthy_drives,
+        drives,
+    };
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use actix_web::{get, HttpRequest, HttpResponse, web};
+use actix_web::web::Query;
+use serde::{Deserialize, Serialize};
+use crate::config_loader::config_struct::Permission;
+use crate::service::stats::{DayCount, DriveShare, PathCount, WINDOWS};
+use crate::State;
+
+/// How many files are in each top list.
+const TOP_FILES: usize = 10;
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    /// like `/dir/file`, to get the downloads of it
+    pub path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct StatsResponse<'a> {
+    generated_at: u64,
+    /// by window, like `24h`
+    top_files: BTreeMap<&'static str, Vec<PathCount>>,
+    per_day: &'a [DayCount],
+    per_drive: &'a [DriveShare],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathStats>,
+}
+
+#[derive(Serialize)]
+struct PathStats {
+    path: String,
+    /// by window, like `24h`
+    downloads: BTreeMap<&'static str, u64>,
+}
+
+/// # Download Statistics API
+/// The most downloaded files in the last 24 hours, 7 days and 30 days, the downloads per day and the share of each drive.
+/// `?path=/dir/file` also gives the downloads of a path. Only the files which the caller can see in the tree are shown.
+/// Needs a `sqlite` or `jsonl` sink, the statistics are computed in background every minute.
+/// Gives `503 Service Unavailable` until the first computation is done.
+#[get("/api/stats")]
+pub async fn get_stats(state: web::Data<State>, query: Query<StatsQuery>, req: HttpRequest) -> HttpResponse {
+    if !state.stats.is_enabled() {
+        return HttpResponse::NotFound().body("No download log to compute statistics from");
+    }
+    let Some(report) = state.stats.report() else {
+        return HttpResponse::ServiceUnavailable().body("The statistics are not computed yet");
+    };
+    let caller = state.auth.caller(&req);
+    // the same as the tree, so that the unlisted files and the files in locked directories are not revealed
+    let can_list = |path: &str| {
+        state.auth.permission(&caller, path) >= Permission::List
+            && state.wheel.is_listed(path)
+            && state.protection.is_unlocked(path, &req)
+    };
+    let top_files = WINDOWS.iter().enumerate()
+        .map(|(index, (label, _))| (*label, report.top_files(index, TOP_FILES, &can_list)))
+        .collect();
+    let path = query.path.as_ref().filter(|path| can_list(path)).map(|path| PathStats {
+        downloads: WINDOWS.iter().enumerate()
+            .map(|(index, (label, _))| (*label, report.downloads(index, path)))
+            .collect(),
+        path: path.clone(),
+    });
+    HttpResponse::Ok().json(StatsResponse {
+        generated_at: report.generated_at,
+        top_files,
+        per_day: &report.per_day,
+        per_drive: &report.per_drive,
+        path,
+    })
+}
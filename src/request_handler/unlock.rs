@@ -0,0 +1,34 @@
+use actix_web::{HttpRequest, HttpResponse, post, web};
+use serde::Deserialize;
+use crate::service::protection::UnlockRejection;
+use crate::request_handler::client_ip;
+use crate::State;
+
+#[derive(Deserialize)]
+pub struct UnlockRequest {
+    /// the protected directory, like `/private`
+    pub path: String,
+    pub password: String,
+}
+
+/// # Unlock API
+/// Unlock a password-protected directory with `{"path": "/private", "password": "xxx"}`.
+/// The response sets a signed cookie, with which the directory can be listed and downloaded from.
+/// Gives `404 Not Found` if the directory is not protected, `401 Unauthorized` if the password is wrong,
+/// and `429 Too Many Requests` after too many wrong passwords from the same ip, or from all the ips together.
+#[post("/api/unlock")]
+pub async fn unlock(state: web::Data<State>, body: web::Json<UnlockRequest>, req: HttpRequest) -> HttpResponse {
+    // paths in `IndexedVfs` start with `/`
+    let path = format!("/{}", body.path.trim_start_matches('/'));
+    if !state.protection.is_protected(path.trim_end_matches('/')) {
+        return HttpResponse::NotFound().finish();
+    }
+    let Some(ip) = client_ip(&req, &state.trusted_proxies) else {
+        return HttpResponse::BadRequest().finish();
+    };
+    match state.protection.unlock(&path, &body.password, ip) {
+        Ok(cookie) => HttpResponse::NoContent().cookie(cookie).finish(),
+        Err(UnlockRejection::WrongPassword) => HttpResponse::Unauthorized().finish(),
+        Err(UnlockRejection::RateLimited) => HttpResponse::TooManyRequests().finish(),
+    }
+}
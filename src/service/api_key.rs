@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use actix_web::HttpRequest;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use crate::config_loader::config_struct::{ApiKeyConfig, ApiKeySetting};
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Why a known API key is refused.
+#[derive(Debug, PartialEq)]
+pub enum KeyRejection {
+    Expired,
+    OutOfScope,
+    RateLimited,
+}
+
+/// A request made with a valid API key.
+pub struct KeyUse {
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
+struct ApiKey {
+    name: String,
+    scopes: Vec<String>,
+    expires_at: Option<DateTime<Utc>>,
+    rate_limit: Option<u32>,
+    roles: Vec<String>,
+}
+
+/// # API Keys
+/// Automated clients send `Authorization: Bearer {key}` instead of solving captchas.
+/// Only the SHA-256 of the keys are kept, the keys are random enough that a slow hash is not needed.
+pub struct ApiKeys {
+    keys: HashMap<String, ApiKey>,
+    /// the start of the current window and the requests in it, by key name
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl ApiKeys {
+    /// Load the keys in config and in the key file.
+    pub fn new(setting: ApiKeySetting) -> Result<Self, String> {
+        let mut configs = setting.keys;
+        if let Some(path) = setting.file {
+            let file = File::open(&path).map_err(|e| format!("Can not open key file {}: {}", path, e))?;
+            let keys: Vec<ApiKeyConfig> = serde_json::from_reader(file)
+                .map_err(|e| format!("Invalid key file {}: {}", path, e))?;
+            configs.extend(keys);
+        }
+        let keys = configs.into_iter().map(|config| {
+            let expires_at = config.expires_at
+                .map(|time| DateTime::parse_from_rfc3339(&time).map(|time| time.with_timezone(&Utc)))
+                .transpose()
+                .map_err(|e| format!("Invalid expires_at of key {}: {}", config.name, e))?;
+            Ok((config.key_hash.to_lowercase(), ApiKey {
+                name: config.name,
+                scopes: config.scopes,
+                expires_at,
+                rate_limit: config.rate_limit,
+                roles: config.roles,
+            }))
+        }).collect::<Result<_, String>>()?;
+        Ok(ApiKeys {
+            keys,
+            windows: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Check the API key of a request to `path`. `Ok(None)` if the request carries no known key.
+    /// Every accepted request counts towards the rate limit of the key.
+    pub fn authenticate(&self, req: &HttpRequest, path: &str) -> Result<Option<KeyUse>, KeyRejection> {
+        let Some(token) = req.headers().get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer ")) else { return Ok(None) };
+        let Some(key) = self.keys.get(&hex::encode(Sha256::digest(token.as_bytes()))) else { return Ok(None) };
+        if key.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(KeyRejection::Expired);
+        }
+        let in_scope = key.scopes.is_empty() || key.scopes.iter().any(|scope| {
+            let prefix = scope.trim_end_matches('/');
+            path == prefix || path.starts_with(&format!("{}/", prefix))
+        });
+        if !in_scope {
+            return Err(KeyRejection::OutOfScope);
+        }
+        if let Some(limit) = key.rate_limit {
+            let mut windows = self.windows.lock().unwrap();
+            let now = Instant::now();
+            let (start, count) = windows.entry(key.name.clone()).or_insert((now, 0));
+            if now.duration_since(*start) >= RATE_LIMIT_WINDOW {
+                *start = now;
+                *count = 0;
+            }
+            if *count >= limit {
+                return Err(KeyRejection::RateLimited);
+            }
+            *count += 1;
+        }
+        Ok(Some(KeyUse {
+            name: key.name.clone(),
+            roles: key.roles.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use super::*;
+
+    #[test]
+    fn test_api_key_scope_and_rate_limit() {
+        let keys = ApiKeys::new(ApiKeySetting {
+            file: None,
+            keys: vec![ApiKeyConfig {
+                name: "ci".to_owned(),
+                key_hash: hex::encode(Sha256::digest(b"secret-key")),
+                scopes: vec!["/releases/".to_owned()],
+                expires_at: Some("2999-01-01T00:00:00Z".to_owned()),
+                rate_limit: Some(2),
+                roles: vec![],
+            }],
+        }).unwrap();
+        let req = |key: &str| TestRequest::default()
+            .insert_header(("Authorization", format!("Bearer {}", key)))
+            .to_http_request();
+
+        assert!(keys.authenticate(&req("other-key"), "/releases/a.zip").unwrap().is_none());
+        assert_eq!(keys.authenticate(&req("secret-key"), "/private/a.zip").err(), Some(KeyRejection::OutOfScope));
+        assert_eq!(keys.authenticate(&req("secret-key"), "/releases/a.zip").unwrap().unwrap().name, "ci");
+        assert!(keys.authenticate(&req("secret-key"), "/releases/b.zip").is_ok());
+        assert_eq!(keys.authenticate(&req("secret-key"), "/releases/c.zip").err(), Some(KeyRejection::RateLimited));
+    }
+}
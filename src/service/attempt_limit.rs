@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// # Failed Attempt Limiter
+/// Counts the wrong guesses of a secret in fixed windows, both by client and target, and by target alone,
+/// so that neither one client nor many clients together can keep guessing the secret of a target.
+/// Each count is kept for at most `max_entries` keys, when more are seen the oldest windows are forgotten first.
+pub struct AttemptLimiter {
+    per_client: u32,
+    per_target: u32,
+    window: Duration,
+    max_entries: usize,
+    counts: Mutex<Counts>,
+}
+
+struct Counts {
+    by_client: HashMap<(IpAddr, String), Window>,
+    by_target: HashMap<String, Window>,
+}
+
+#[derive(Clone, Copy)]
+struct Window {
+    start: Instant,
+    failures: u32,
+}
+
+impl AttemptLimiter {
+    pub fn new(per_client: u32, per_target: u32, window: Duration, max_entries: usize) -> Self {
+        AttemptLimiter {
+            per_client,
+            per_target,
+            window,
+            max_entries,
+            counts: Mutex::new(Counts {
+                by_client: HashMap::new(),
+                by_target: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Whether `client` is refused to try `target` until the window passes.
+    pub fn is_limited(&self, client: IpAddr, target: &str) -> bool {
+        let now = Instant::now();
+        let counts = self.counts.lock().unwrap();
+        failures(&counts.by_client, &(client, target.to_owned()), now, self.window) >= self.per_client
+            || failures(&counts.by_target, &target.to_owned(), now, self.window) >= self.per_target
+    }
+
+    pub fn record_failure(&self, client: IpAddr, target: &str) {
+        let now = Instant::now();
+        let mut counts = self.counts.lock().unwrap();
+        increase(&mut counts.by_client, (client, target.to_owned()), now, self.window, self.max_entries);
+        increase(&mut counts.by_target, target.to_owned(), now, self.window, self.max_entries);
+    }
+}
+
+fn failures<K: Eq + Hash>(map: &HashMap<K, Window>, key: &K, now: Instant, window: Duration) -> u32 {
+    map.get(key)
+        .filter(|counted| now.duration_since(counted.start) < window)
+        .map(|counted| counted.failures)
+        .unwrap_or(0)
+}
+
+fn increase<K: Eq + Hash + Clone>(map: &mut HashMap<K, Window>, key: K, now: Instant, window: Duration, max_entries: usize) {
+    if !map.contains_key(&key) && map.len() >= max_entries {
+        map.retain(|_, counted| now.duration_since(counted.start) < window);
+        if map.len() >= max_entries {
+            let oldest = map.iter().min_by_key(|(_, counted)| counted.start).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                map.remove(&oldest);
+            }
+        }
+    }
+    let counted = map.entry(key).or_insert(Window { start: now, failures: 0 });
+    if now.duration_since(counted.start) >= window {
+        *counted = Window { start: now, failures: 0 };
+    }
+    counted.failures += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attempt_limit() {
+        let limiter = AttemptLimiter::new(2, 3, Duration::from_secs(60), 2);
+        let ip = |last: u8| IpAddr::from([10, 0, 0, last]);
+        limiter.record_failure(ip(1), "a");
+        assert!(!limiter.is_limited(ip(1), "a"));
+        limiter.record_failure(ip(1), "a");
+        assert!(limiter.is_limited(ip(1), "a"));
+        assert!(!limiter.is_limited(ip(2), "a"));
+        assert!(!limiter.is_limited(ip(1), "b"));
+        // many clients together
+        limiter.record_failure(ip(2), "a");
+        assert!(limiter.is_limited(ip(3), "a"));
+        // the map is capped, an old window is forgotten
+        limiter.record_failure(ip(3), "b");
+        let counts = limiter.counts.lock().unwrap();
+        assert_eq!(counts.by_client.len(), 2);
+        assert!(counts.by_client.contains_key(&(ip(3), "b".to_owned())));
+    }
+}
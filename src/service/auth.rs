@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::HttpRequest;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use rand::RngCore;
+use serde::Serialize;
+use crate::config_loader::config_struct::{AclRule, AuthSetting, Permission};
+use crate::service::attempt_limit::AttemptLimiter;
+use crate::service::signer::Signer;
+
+pub const SESSION_COOKIE: &str = "rlist_session";
+/// After this many wrong passwords for a user from an ip, logging in as the user is refused until the window passes.
+const MAX_FAILED_LOGINS: u32 = 10;
+/// After this many wrong passwords for a user from all the ips, logging in as the user is refused for everyone until the window passes.
+const MAX_FAILED_LOGINS_PER_USER: u32 = 100;
+const FAILED_LOGIN_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// The most ips, and the most names, whose wrong passwords are counted at once.
+const MAX_TRACKED_LOGINS: usize = 10000;
+/// The role everyone has, including the anonymous.
+pub const EVERYONE: &str = "*";
+
+/// # Caller
+/// Who is making the request, anonymous callers have no name and no role except `EVERYONE`.
+#[derive(Serialize)]
+pub struct Caller {
+    pub name: Option<String>,
+    pub roles: Vec<String>,
+}
+
+impl Caller {
+    pub fn anonymous() -> Self {
+        Caller {
+            name: None,
+            roles: Vec::new(),
+        }
+    }
+
+    fn has_any(&self, roles: &[String]) -> bool {
+        roles.iter().any(|role| role == EVERYONE || self.roles.contains(role))
+    }
+}
+
+/// Why a user is not logged in.
+#[derive(Debug, PartialEq)]
+pub enum LoginRejection {
+    /// the user does not exist, or the password is wrong
+    WrongPassword,
+    /// too many wrong passwords recently
+    RateLimited,
+}
+
+struct User {
+    password_hash: String,
+    roles: Vec<String>,
+}
+
+/// # Auth
+/// Users log in with their passwords and get a signed session token, which can be sent as a cookie or `Authorization: Bearer {token}`.
+/// The permission of a path is decided by the ACL rules with the most specific path matching it,
+/// the caller gets the highest permission granted to any of its roles by these rules.
+/// Paths matched by no rule can be read by everyone.
+pub struct Auth {
+    users: HashMap<String, User>,
+    rules: Vec<AclRule>,
+    signer: Signer,
+    max_age: Duration,
+    /// verified against when the user does not exist, so that it takes as long as a wrong password
+    dummy_hash: String,
+    failures: AttemptLimiter,
+}
+
+impl Auth {
+    /// Fails if any password hash is not a valid PHC string.
+    pub fn new(setting: AuthSetting) -> Result<Self, String> {
+        let users = setting.users.into_iter().map(|user| {
+            PasswordHash::new(&user.password_hash)
+                .map_err(|e| format!("Invalid password hash of {}: {}", user.name, e))?;
+            Ok((user.name, User {
+                password_hash: user.password_hash,
+                roles: user.roles,
+            }))
+        }).collect::<Result<_, String>>()?;
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let salt = SaltString::encode_b64(&salt).map_err(|e| e.to_string())?;
+        let dummy_hash = Argon2::default().hash_password(b"", &salt).map_err(|e| e.to_string())?.to_string();
+        Ok(Auth {
+            users,
+            rules: setting.rules,
+            signer: Signer::new("session", setting.secret),
+            max_age: Duration::from_secs(setting.session_max_age),
+            dummy_hash,
+            failures: AttemptLimiter::new(MAX_FAILED_LOGINS, MAX_FAILED_LOGINS_PER_USER, FAILED_LOGIN_WINDOW, MAX_TRACKED_LOGINS),
+        })
+    }
+
+    /// A session token of the user for the client at `ip`, which must not come from a header.
+    /// A password is verified even if the user does not exist, so that the time taken does not tell whether the user exists.
+    /// In `FAILED_LOGIN_WINDOW`, each ip can only try `MAX_FAILED_LOGINS` wrong passwords of a user,
+    /// and all the ips together `MAX_FAILED_LOGINS_PER_USER`, the limited ones are refused before hashing anything.
+    pub fn login(&self, name: &str, password: &str, ip: IpAddr) -> Result<String, LoginRejection> {
+        if self.failures.is_limited(ip, name) {
+            return Err(LoginRejection::RateLimited);
+        }
+        let user = self.users.get(name);
+        let password_hash = user.map_or(&self.dummy_hash, |user| &user.password_hash);
+        let verified = PasswordHash::new(password_hash)
+            .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+            .unwrap_or(false);
+        if user.is_none() || !verified {
+            self.failures.record_failure(ip, name);
+            return Err(LoginRejection::WrongPassword);
+        }
+        // the name is hex encoded, so that it can not be confused with the separator
+        Ok(format!("{}.{}", hex::encode(name), self.signer.issue(name, self.max_age)))
+    }
+
+    pub fn session_max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    pub fn session_cookie(&self, token: String) -> Cookie<'static> {
+        Cookie::build(SESSION_COOKIE, token)
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .max_age(actix_web::cookie::time::Duration::seconds(self.max_age.as_secs() as i64))
+            .finish()
+    }
+
+    /// The caller of the request by its session token, anonymous if it has no valid one.
+    pub fn caller(&self, req: &HttpRequest) -> Caller {
+        let bearer = req.headers().get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.to_owned());
+        let token = bearer.or_else(|| req.cookie(SESSION_COOKIE).map(|cookie| cookie.value().to_owned()));
+        token.and_then(|token| self.verify_session(&token)).unwrap_or_else(Caller::anonymous)
+    }
+
+    fn verify_session(&self, token: &str) -> Option<Caller> {
+        let (name, signed) = token.split_once('.')?;
+        let name = String::from_utf8(hex::decode(name).ok()?).ok()?;
+        if !self.signer.verify(&name, signed) {
+            return None;
+        }
+        // users removed from config are logged out
+        let user = self.users.get(&name)?;
+        Some(Caller {
+            roles: user.roles.clone(),
+            name: Some(name),
+        })
+    }
+
+    /// The permission of the caller on `path`, like `/dir/file`.
+    pub fn permission(&self, caller: &Caller, path: &str) -> Permission {
+        let prefix_of = |rule: &AclRule| rule.path.trim_end_matches('/').len();
+        let matched: Vec<&AclRule> = self.rules.iter()
+            .filter(|rule| {
+                let prefix = rule.path.trim_end_matches('/');
+                path == prefix || path.starts_with(&format!("{}/", prefix))
+            })
+            .collect();
+        let Some(most_specific) = matched.iter().map(|rule| prefix_of(rule)).max() else {
+            return Permission::Read;
+        };
+        matched.into_iter()
+            .filter(|rule| prefix_of(rule) == most_specific && caller.has_any(&rule.roles))
+            .map(|rule| rule.permission)
+            .max()
+            .unwrap_or(Permission::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use argon2::password_hash::{PasswordHasher, SaltString};
+    use crate::config_loader::config_struct::UserConfig;
+    use super::*;
+
+    fn rule(path: &str, roles: &[&str], permission: Permission) -> AclRule {
+        AclRule {
+            path: path.to_owned(),
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+            permission,
+        }
+    }
+
+    #[test]
+    fn test_auth_permission() {
+        let salt = SaltString::encode_b64(b"some salt").unwrap();
+        let password_hash = Argon2::default().hash_password(b"password", &salt).unwrap().to_string();
+        let auth = Auth::new(AuthSetting {
+            users: vec![UserConfig { name: "alice".to_owned(), password_hash, roles: vec!["staff".to_owned()] }],
+            rules: vec![
+                rule("/team", &["*"], Permission::List),
+                rule("/team", &["staff"], Permission::Read),
+                rule("/team/hr/", &["hr"], Permission::Read),
+            ],
+            ..Default::default()
+        }).unwrap();
+        let ip = IpAddr::from([10, 0, 0, 1]);
+        assert_eq!(auth.login("alice", "wrong", ip).unwrap_err(), LoginRejection::WrongPassword);
+        let token = auth.login("alice", "password", ip).unwrap();
+        let alice = auth.verify_session(&token).unwrap();
+        let anonymous = Caller::anonymous();
+
+        assert_eq!(auth.permission(&anonymous, "/public/file"), Permission::Read);
+        assert_eq!(auth.permission(&anonymous, "/team/file"), Permission::List);
+        assert_eq!(auth.permission(&alice, "/team/file"), Permission::Read);
+        // the most specific rules override the others
+        assert_eq!(auth.permission(&alice, "/team/hr/file"), Permission::None);
+        assert_eq!(auth.permission(&alice, "/teammate"), Permission::Read);
+
+        for _ in 1..MAX_FAILED_LOGINS {
+            assert!(auth.login("alice", "wrong", ip).is_err());
+        }
+        assert_eq!(auth.login("alice", "password", ip).unwrap_err(), LoginRejection::RateLimited);
+        assert!(auth.login("alice", "password", IpAddr::from([10, 0, 0, 2])).is_ok());
+    }
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use crate::service::captcha::Verify;
+use crate::service::metrics::METRICS;
 
 const API_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
 
@@ -43,7 +44,7 @@ impl Verify for CloudflareTurnstile {
             .form(&form)
             .send()
             .await;
-        match response {
+        let passed = match response {
             Ok(response) => {
                 let response = response.json::<Response>().await;
                 match response {
@@ -51,13 +52,14 @@ impl Verify for CloudflareTurnstile {
                         response.success
                     }
                     Err(_) => {
-                        return false;
+                        false
                     }
                 }
             }
             Err(_) => {
-                return false;
+                false
             }
-        }
+        };
+        METRICS.captcha_result(passed)
     }
 }
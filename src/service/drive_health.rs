@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tracing::warn;
+use crate::service::webhook::{WebhookEvent, Webhooks};
+
+/// # Drive Health
+/// Records the drives which failed. Links on these drives will not be offered until the drive is marked healthy again,
+/// by a successful probe or download. Failed drives are probed again once `cooldown` passes since the last failure.
+pub struct DriveHealth {
+    records: RwLock<HashMap<String, HealthRecord>>,
+    cooldown: Duration,
+    webhooks: Arc<Webhooks>,
+}
+
+#[derive(Default)]
+struct HealthRecord {
+    failed_at: Option<Instant>,
+    last_error: Option<String>,
+    last_probe: Option<SystemTime>,
+}
+
+#[derive(Serialize)]
+/// The health of a drive, as shown in the admin api.
+pub struct DriveHealthReport {
+    pub drive: String,
+    pub healthy: bool,
+    pub last_error: Option<String>,
+    /// in milliseconds since unix epoch
+    pub last_probe: Option<u128>,
+}
+
+impl DriveHealth {
+    pub fn new(cooldown: Duration, webhooks: Arc<Webhooks>) -> Self {
+        DriveHealth {
+            records: RwLock::new(HashMap::new()),
+            cooldown,
+            webhooks,
+        }
+    }
+
+    /// The webhooks are notified when a healthy drive becomes unhealthy, so only once for concurrent failures.
+    pub fn mark_failed(&self, drive: &str, reason: String) {
+        warn!("Drive {} is marked unhealthy: {}", drive, reason);
+        let was_healthy = {
+            let mut records = self.records.write().unwrap();
+            let record = records.entry(drive.to_owned()).or_default();
+            record.last_error = Some(reason.clone());
+            record.failed_at.replace(Instant::now()).is_none()
+        };
+        if was_healthy {
+            self.webhooks.notify(WebhookEvent::DriveUnhealthy { drive: drive.to_owned(), reason });
+        }
+    }
+
+    pub fn mark_healthy(&self, drive: &str) {
+        let mut records = self.records.write().unwrap();
+        if let Some(record) = records.get_mut(drive) {
+            record.failed_at = None;
+        }
+    }
+
+    /// Record the result of a health check of the drive.
+    pub fn record_probe(&self, drive: &str, result: Result<(), String>) {
+        match result {
+            Ok(()) => self.mark_healthy(drive),
+            Err(reason) => self.mark_failed(drive, reason),
+        }
+        let mut records = self.records.write().unwrap();
+        records.entry(drive.to_owned()).or_default().last_probe = Some(SystemTime::now());
+    }
+
+    pub fn is_healthy(&self, drive: &str) -> bool {
+        let records = self.records.read().unwrap();
+        records.get(drive).and_then(|record| record.failed_at).is_none()
+    }
+
+    /// Whether the drive is unhealthy and its `cooldown` has passed, so it should be probed again.
+    pub fn probe_due(&self, drive: &str) -> bool {
+        let records = self.records.read().unwrap();
+        records.get(drive).and_then(|record| record.failed_at).is_some_and(|at| at.elapsed() >= self.cooldown)
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    pub fn report(&self, drive: &str) -> DriveHealthReport {
+        let healthy = self.is_healthy(drive);
+        let records = self.records.read().unwrap();
+        let record = records.get(drive);
+        DriveHealthReport {
+            drive: drive.to_owned(),
+            healthy,
+            last_error: record.and_then(|record| record.last_error.clone()),
+            last_probe: record.and_then(|record| record.last_probe)
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| time.as_millis()),
+        }
+    }
+}
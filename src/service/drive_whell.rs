@@ -1,128 +1,372 @@
-use std::cell::UnsafeCell;
-use std::sync::{Arc};
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use arc_swap::ArcSwap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
 use tokio::time::interval;
-use tracing::error;
-use crate::config_loader::config_struct::DriveConfig;
-use crate::driver::CloudDriver;
-use crate::driver::{OneDriveDriver};
+use tracing::{error, info, info_span, Instrument};
+use crate::config_loader::config_struct::{CacheSetting, ConflictPolicy, DriveConfig, MergeSetting, VisibilitySetting};
+use crate::driver::Drive;
+use crate::driver::token_store::TokenStore;
+use crate::service::metrics::METRICS;
+use crate::service::protection::Protection;
+use crate::service::webhook::{WebhookEvent, Webhooks};
 use crate::vfs::combine::{CombinableVfsDir, CombinableVfsFile, combine_vfs_dirs};
+use crate::vfs::diff::{FileIndex, index_files, TreeDiff};
 use crate::vfs::hide_url::{hide_url_for_dir, UrlHiddenDir};
 use crate::vfs::path_compress::IndexedVfs;
+use crate::vfs::visibility::PathFilter;
 
 
 type PathMap = IndexedVfs<CombinableVfsFile, CombinableVfsDir>;
 pub struct DriveWheel {
-    path_map: UnsafeCell<Arc<PathMap>>,
-    hidden_url: UnsafeCell<Arc<UrlHiddenDir>>,
-    drive_config: Vec<DriveConfig>,
-    stop_signal: UnsafeCell<StopSignal>,
+    // swapped by refreshes while requests read them
+    path_map: ArcSwap<PathMap>,
+    hidden_url: ArcSwap<UrlHiddenDir>,
+    drives: Vec<Drive>,
+    /// the state of `drives[i]` is `states[i]`
+    states: Vec<Mutex<DriveState>>,
+    conflict: ConflictPolicy,
+    /// global `hide` and `unlisted` patterns, applied to the combined tree
+    filter: PathFilter,
+    protection: Arc<Protection>,
+    /// increased every time the combined tree is rebuilt
+    generation: AtomicU64,
+    /// only one refresh at a time, periodic or triggered by admin
+    refresh_lock: tokio::sync::Mutex<()>,
+    /// the files of the current tree, to find the changes of the next one
+    files: Mutex<Option<FileIndex>>,
+    changes: Mutex<ChangeHistory>,
+    webhooks: Arc<Webhooks>,
+    /// set when dropped, to stop the refresh loop
+    stop_signal: AtomicBool,
 }
 
-unsafe impl Send for DriveWheel {}
-unsafe impl Sync for DriveWheel {}
-
-async fn get_vfs(drive_config: &Vec<DriveConfig>) -> CombinableVfsDir {
-    let drives: Vec<_> = drive_config.iter()
-        .map(|config| async move {
-            match config {
-                DriveConfig::Onedrive(config) => {
-                    match OneDriveDriver::new(config).await {
-                        Ok(driver) => Some(driver.into_combinable()),
-                        Err(e) => {
-                            error!("Failed to create driver: {}", e);
-                            None
-                        }
-                    }
-                }
-            }
-        }).collect();
-    let drives = futures::future::join_all(drives).await;
-    let drives: Vec<_> = drives.into_iter().filter_map(|x| x).collect();
-    return combine_vfs_dirs(drives);
+/// What `DriveWheel` remembers about a drive across refreshes.
+struct DriveState {
+    enabled: bool,
+    /// the last successfully loaded tree, kept when the drive fails to load
+    tree: Option<CombinableVfsDir>,
+    last_refresh: Option<SystemTime>,
+    last_error: Option<String>,
+    error_count: u64,
+}
+
+/// # Tree Change
+/// What changed in the tree when it was rebuilt as `generation`.
+#[derive(Serialize)]
+pub struct TreeChange {
+    pub generation: u64,
+    /// in milliseconds since unix epoch
+    pub timestamp: u128,
+    #[serde(flatten)]
+    pub diff: TreeDiff,
+}
+
+/// The latest changes of the tree, the oldest first. Rebuilds without any change are not kept.
+struct ChangeHistory {
+    changes: VecDeque<Arc<TreeChange>>,
+    capacity: usize,
+    /// the changes up to this generation are no longer kept, the first tree counts as forgotten
+    forgotten: u64,
+}
+
+/// Why a refresh or a change of the drives is not done.
+#[derive(Debug)]
+pub enum WheelError {
+    /// no drive has the name
+    UnknownDrive(String),
+    /// the tree can not be rebuilt, the last one is kept
+    Rebuild(String),
+}
+
+impl fmt::Display for WheelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WheelError::UnknownDrive(name) => write!(f, "No drive named {}", name),
+            WheelError::Rebuild(error) => f.write_str(error),
+        }
+    }
+}
+
+#[derive(Serialize)]
+/// The status of a drive, as shown in the admin api. Times are in milliseconds since unix epoch.
+pub struct DriveStatus {
+    pub drive: String,
+    pub enabled: bool,
+    pub last_refresh: Option<u128>,
+    pub last_error: Option<String>,
+    pub error_count: u64,
+    pub item_count: u64,
+    pub token_expires_at: Option<u128>,
+}
+
+fn to_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
 }
 
 impl DriveWheel {
-    async fn new_data(drive_config: &Vec<DriveConfig>) -> (Arc<PathMap>, Arc<UrlHiddenDir>) {
-        let vfs = get_vfs(drive_config).await;
-        let hidden = hide_url_for_dir(&vfs);
+    fn new_data(&self, vfs: CombinableVfsDir) -> (Arc<PathMap>, Arc<UrlHiddenDir>) {
+        METRICS.tree_items.with_label_values(&["all"]).set(vfs.item_count() as i64);
+        let hidden = hide_url_for_dir(&vfs, "", &|path| self.protection.is_protected(path));
         let compressed_path = IndexedVfs::new(vfs);
-        return (
+        (
             Arc::new(compressed_path),
             Arc::new(hidden)
-        );
+        )
     }
     fn refresh(&self, data: (Arc<PathMap>, Arc<UrlHiddenDir>)) {
-        let (_path_map, _hidden_url) = data;
-        let path_map = self.path_map.get();
-        let hidden_url = self.hidden_url.get();
-        unsafe {
-            *path_map = _path_map;
-            *hidden_url = _hidden_url;
-        }
+        let (path_map, hidden_url) = data;
+        self.path_map.store(path_map);
+        self.hidden_url.store(hidden_url);
     }
-    pub async fn new(drive_config: Vec<DriveConfig>, refresh_time: u64) -> Arc<DriveWheel> {
-        let (compressed_path, hidden_url) = Self::new_data(&drive_config).await;
-        let stop_signal = StopSignal::new();
+    /// The drives are loaded in background, the tree is empty and the generation is 0 until it is done.
+    pub fn new(drive_config: Vec<DriveConfig>, cache: CacheSetting, merge: MergeSetting, visibility: VisibilitySetting, protection: Arc<Protection>, token_store: Arc<TokenStore>, webhooks: Arc<Webhooks>) -> Arc<DriveWheel> {
+        let refresh_time = cache.refresh_interval;
+        let change_history = cache.change_history;
+        let link_max_age = Duration::from_secs(cache.link_max_age);
+        let conflict = merge.conflict;
+        let drives: Vec<Drive> = drive_config.into_iter().enumerate()
+            .map(|(index, config)| Drive::new(index, config, token_store.clone(), link_max_age))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| panic!("Invalid drive config: {}", e));
+        let filter = PathFilter::new(&visibility.hide, &visibility.unlisted)
+            .unwrap_or_else(|e| panic!("Invalid visibility config: {}", e));
+        let states = drives.iter().map(|_| Mutex::new(DriveState {
+            enabled: true,
+            tree: None,
+            last_refresh: None,
+            last_error: None,
+            error_count: 0,
+        })).collect();
+        // replaced by the first successful refresh
+        let empty = CombinableVfsDir::new(String::new(), Vec::new(), Vec::new(), 0);
         let instance = Arc::new(DriveWheel {
-            path_map: UnsafeCell::new(compressed_path.clone()),
-            hidden_url: UnsafeCell::new(hidden_url.clone()),
-            drive_config,
-            stop_signal,
+            path_map: ArcSwap::from_pointee(IndexedVfs::new(empty.clone())),
+            hidden_url: ArcSwap::from_pointee(hide_url_for_dir(&empty, "", &|_| false)),
+            drives,
+            states,
+            conflict,
+            filter,
+            protection,
+            generation: AtomicU64::new(0),
+            refresh_lock: tokio::sync::Mutex::new(()),
+            files: Mutex::new(None),
+            changes: Mutex::new(ChangeHistory {
+                changes: VecDeque::new(),
+                capacity: change_history,
+                forgotten: 1,
+            }),
+            webhooks,
+            stop_signal: AtomicBool::new(false),
         });
         let instance_clone = instance.clone();
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(refresh_time));
+            // the first tick completes immediately, so the first tree is loaded right away
             loop {
                 interval.tick().await;
-                if unsafe {
-                    match instance_clone.stop_signal.get().as_ref() {
-                        Some(signal) => signal.is_stop(),
-                        None => true
-                    }
-                } {
+                if instance_clone.stop_signal.load(Ordering::SeqCst) {
                     break;
                 }
-                let data = Self::new_data(&instance_clone.drive_config).await;
-                instance_clone.refresh(data);
+                // an empty tree is served until a refresh succeeds
+                if let Err(e) = instance_clone.refresh_all().await {
+                    error!("Failed to refresh drives, the last tree is kept: {}", e);
+                }
             }
         });
-        return instance;
+        instance
     }
-    pub fn get_path_map(&self) -> Arc<PathMap> {
-        unsafe {
-            (&*self.path_map.get()).clone()
+
+    /// Load all the enabled drives again and rebuild the tree, gives the new generation.
+    pub async fn refresh_all(&self) -> Result<u64, WheelError> {
+        let _lock = self.refresh_lock.lock().await;
+        let indices: Vec<usize> = (0..self.drives.len()).filter(|&index| self.state(index).enabled).collect();
+        self.load_drives(&indices).await;
+        self.rebuild(true)
+    }
+
+    /// Load one drive again and rebuild the tree with the last loaded trees of the others, gives the new generation.
+    pub async fn refresh_drive(&self, name: &str) -> Result<u64, WheelError> {
+        let index = self.index_of(name)?;
+        let _lock = self.refresh_lock.lock().await;
+        self.load_drives(&[index]).await;
+        self.rebuild(true)
+    }
+
+    /// Enable or disable a drive at runtime, the files only on disabled drives are removed from the tree.
+    /// A drive which has never been loaded is loaded when enabled. Gives the new generation.
+    /// The drive is left as it was if the tree can not be rebuilt, like when disabling the last drive.
+    pub async fn set_enabled(&self, name: &str, enabled: bool) -> Result<u64, WheelError> {
+        let index = self.index_of(name)?;
+        let _lock = self.refresh_lock.lock().await;
+        let (was_enabled, needs_load) = {
+            let mut state = self.state(index);
+            let was_enabled = state.enabled;
+            state.enabled = enabled;
+            (was_enabled, enabled && state.tree.is_none())
+        };
+        if needs_load {
+            self.load_drives(&[index]).await;
         }
+        // the files of the drive did not change, so they are not posted as added or removed
+        let result = self.rebuild(false);
+        if result.is_err() {
+            self.state(index).enabled = was_enabled;
+        }
+        result
     }
-    pub fn get_hidden_url(&self) -> Arc<UrlHiddenDir> {
-        unsafe {
-            (&*self.hidden_url.get()).clone()
+
+    /// Load the drives concurrently, failed ones keep their last loaded trees.
+    async fn load_drives(&self, indices: &[usize]) {
+        let loads = indices.iter().map(|&index| {
+            let drive = &self.drives[index];
+            async move {
+                let started = Instant::now();
+                let result = drive.load().await;
+                let elapsed = started.elapsed();
+                METRICS.refresh_duration.with_label_values(&[drive.name()]).observe(elapsed.as_secs_f64());
+                if result.is_ok() {
+                    info!(elapsed_ms = elapsed.as_millis() as u64, "Drive is loaded");
+                }
+                (index, result)
+            }.instrument(info_span!("refresh", drive = drive.name()))
+        });
+        for (index, result) in futures::future::join_all(loads).await {
+            let mut state = self.state(index);
+            state.last_refresh = Some(SystemTime::now());
+            match result {
+                Ok(tree) => {
+                    METRICS.tree_items.with_label_values(&[self.drives[index].name()]).set(tree.item_count() as i64);
+                    state.tree = Some(tree);
+                    state.last_error = None;
+                }
+                Err(e) => {
+                    error!("Failed to create driver {}: {}", self.drives[index].name(), e);
+                    self.webhooks.notify(WebhookEvent::RefreshFailed {
+                        drive: self.drives[index].name().to_owned(),
+                        error: e.clone(),
+                    });
+                    state.last_error = Some(e);
+                    state.error_count += 1;
+                    METRICS.refresh_errors.with_label_values(&[self.drives[index].name()]).inc();
+                }
+            }
         }
     }
-}
 
-impl Drop for DriveWheel {
-    fn drop(&mut self) {
-        unsafe {
-            self.stop_signal.get().as_mut().map(|signal| signal.stop());
+    /// Combine the trees of the enabled drives, and replace the current tree with it.
+    /// The changes from the last tree are recorded, and the new files are posted to the webhooks, except on the first build.
+    /// Without `track_changes`, the changes before are marked forgotten instead, so that clients fetch the whole tree.
+    fn rebuild(&self, track_changes: bool) -> Result<u64, WheelError> {
+        let trees: Vec<CombinableVfsDir> = self.states.iter()
+            .filter_map(|state| {
+                let state = state.lock().unwrap();
+                if state.enabled { state.tree.clone() } else { None }
+            })
+            .collect();
+        if trees.is_empty() {
+            return Err(WheelError::Rebuild("No drive is loaded".to_owned()));
         }
+        let vfs = combine_vfs_dirs(trees, self.conflict).map_err(WheelError::Rebuild)?.apply_filter(&self.filter, "");
+        let files = index_files(&vfs);
+        self.refresh(self.new_data(vfs));
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let previous = self.files.lock().unwrap().replace(files.clone());
+        if !track_changes {
+            self.changes.lock().unwrap().forgotten = generation;
+        } else if let Some(previous) = previous {
+            let diff = TreeDiff::new(&previous, &files);
+            // webhooks can not unlock, so the files in protected directories are never posted
+            let added = diff.filter(&|path| !self.protection.in_protected(path)).added;
+            if !added.is_empty() {
+                self.webhooks.notify(WebhookEvent::NewFiles { generation, files: added });
+            }
+            if !diff.is_empty() {
+                self.record_change(generation, diff);
+            }
+        }
+        Ok(generation)
     }
-}
 
-struct StopSignal {
-    stop: bool
-}
+    fn record_change(&self, generation: u64, diff: TreeDiff) {
+        let mut history = self.changes.lock().unwrap();
+        history.changes.push_back(Arc::new(TreeChange {
+            generation,
+            timestamp: to_millis(SystemTime::now()),
+            diff,
+        }));
+        while history.changes.len() > history.capacity {
+            if let Some(change) = history.changes.pop_front() {
+                history.forgotten = change.generation;
+            }
+        }
+    }
 
-impl StopSignal {
-    pub fn new() -> UnsafeCell<Self> {
-        UnsafeCell::new(StopSignal {
-            stop: false
-        })
+    /// The changes after the tree of generation `since`, the oldest first,
+    /// and whether they are complete, as the old ones are forgotten.
+    pub fn changes_since(&self, since: u64) -> (Vec<Arc<TreeChange>>, bool) {
+        let history = self.changes.lock().unwrap();
+        let changes = history.changes.iter().filter(|change| change.generation > since).cloned().collect();
+        (changes, since >= history.forgotten)
     }
-    pub fn is_stop(&self) -> bool {
-        self.stop
+
+    fn index_of(&self, name: &str) -> Result<usize, WheelError> {
+        self.drives.iter().position(|drive| drive.name() == name)
+            .ok_or_else(|| WheelError::UnknownDrive(name.to_owned()))
+    }
+
+    fn state(&self, index: usize) -> MutexGuard<'_, DriveState> {
+        self.states[index].lock().unwrap()
     }
-    pub fn stop(&mut self) {
-        self.stop = true;
+
+    pub fn drives(&self) -> &[Drive] {
+        &self.drives
     }
-}
\ No newline at end of file
+    /// The drives which are not disabled.
+    pub fn enabled_drives(&self) -> Vec<&Drive> {
+        self.drives.iter().enumerate()
+            .filter(|(index, _)| self.state(*index).enabled)
+            .map(|(_, drive)| drive)
+            .collect()
+    }
+    /// The generation of the current tree, increased every time the tree is rebuilt.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+    /// Whether `path` is a file listed in the current tree, so not hidden, unlisted or in an unlisted directory.
+    pub fn is_listed(&self, path: &str) -> bool {
+        self.files.lock().unwrap().as_ref().is_some_and(|files| files.contains_key(path))
+    }
+    pub async fn status(&self) -> Vec<DriveStatus> {
+        let mut status = Vec::with_capacity(self.drives.len());
+        for (index, drive) in self.drives.iter().enumerate() {
+            let token_expires_at = drive.token_expires_at().await.map(to_millis);
+            let state = self.state(index);
+            status.push(DriveStatus {
+                drive: drive.name().to_owned(),
+                enabled: state.enabled,
+                last_refresh: state.last_refresh.map(to_millis),
+                last_error: state.last_error.clone(),
+                error_count: state.error_count,
+                item_count: state.tree.as_ref().map(|tree| tree.item_count()).unwrap_or(0),
+                token_expires_at,
+            });
+        }
+        status
+    }
+    pub fn get_path_map(&self) -> Arc<PathMap> {
+        self.path_map.load_full()
+    }
+    pub fn get_hidden_url(&self) -> Arc<UrlHiddenDir> {
+        self.hidden_url.load_full()
+    }
+}
+
+impl Drop for DriveWheel {
+    fn drop(&mut self) {
+        self.stop_signal.store(true, Ordering::SeqCst);
+    }
+}
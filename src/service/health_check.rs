@@ -0,0 +1,30 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::interval;
+use crate::service::drive_health::DriveHealth;
+use crate::service::drive_whell::DriveWheel;
+
+/// Probe all the enabled drives every `period` in background, and mark them healthy or unhealthy,
+/// so that the links on a broken drive (like a suspended account) will not be offered.
+/// An unhealthy drive is probed again as soon as its cooldown passes, rather than waiting for the next `period`.
+pub fn spawn_health_check(wheel: Arc<DriveWheel>, health: Arc<DriveHealth>, period: Duration) {
+    tokio::spawn(async move {
+        let mut interval = interval(period.min(health.cooldown()).max(Duration::from_secs(1)));
+        let mut last_full_check: Option<Instant> = None;
+        loop {
+            interval.tick().await;
+            let full_check = last_full_check.is_none_or(|at| at.elapsed() >= period);
+            if full_check {
+                last_full_check = Some(Instant::now());
+            }
+            let probes = wheel.enabled_drives().into_iter()
+                .filter(|drive| full_check || health.probe_due(drive.name()))
+                .map(|drive| async move {
+                    (drive.name(), drive.probe().await)
+                });
+            for (name, result) in futures::future::join_all(probes).await {
+                health.record_probe(name, result);
+            }
+        }
+    });
+}
@@ -0,0 +1,49 @@
+use std::path::Path;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{EnvFilter, fmt, Layer, Registry};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use crate::config_loader::config_struct::{LogFormat, LogSetting};
+
+/// # Logging
+/// Print the log to stdout, and append it to the log file if configured.
+/// `RUST_LOG` overrides the configured level, so that a deployment can be debugged without editing the config.
+/// The returned guard flushes the log file when dropped, so it should be kept until exit.
+pub fn init_logging(setting: &LogSetting) -> Result<Option<WorkerGuard>, String> {
+    let filter = match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) => EnvFilter::try_new(directives),
+        Err(_) => EnvFilter::try_new(&setting.level),
+    }.map_err(|e| format!("Invalid log level: {}", e))?;
+    let (file, guard) = match &setting.file {
+        Some(path) => {
+            let path = Path::new(path);
+            let file_name = path.file_name().ok_or_else(|| format!("Invalid log file {}", path.display()))?;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let (writer, guard) = tracing_appender::non_blocking(tracing_appender::rolling::never(dir, file_name));
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+    let layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = match setting.format {
+        LogFormat::Pretty => {
+            let mut layers = vec![fmt::layer().boxed()];
+            if let Some(file) = file {
+                layers.push(fmt::layer().with_ansi(false).with_writer(file).boxed());
+            }
+            layers
+        }
+        LogFormat::Json => {
+            let mut layers = vec![fmt::layer().json().with_current_span(true).with_span_list(true).boxed()];
+            if let Some(file) = file {
+                layers.push(fmt::layer().json().with_current_span(true).with_span_list(true).with_writer(file).boxed());
+            }
+            layers
+        }
+    };
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .try_init()
+        .map_err(|e| format!("Failed to initialize logging: {}", e))?;
+    Ok(guard)
+}
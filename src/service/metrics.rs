@@ -0,0 +1,137 @@
+use std::sync::LazyLock;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+
+/// # Metrics
+/// Counters exposed at `/metrics` in Prometheus text format.
+/// They are global, so that they can be fed from anywhere without threading a handle through.
+pub struct Metrics {
+    registry: Registry,
+    /// by route pattern, method and status
+    pub requests: IntCounterVec,
+    /// in seconds, by route pattern, method and status
+    pub request_duration: HistogramVec,
+    /// download links given out, by the drive of the selected mirror
+    pub downloads: IntCounterVec,
+    /// by `result`, `pass` or `fail`
+    pub captcha: IntCounterVec,
+    /// in seconds, by drive
+    pub refresh_duration: HistogramVec,
+    /// by drive
+    pub refresh_errors: IntCounterVec,
+    /// files and directories in the tree of each drive, and `all` for the combined tree
+    pub tree_items: IntGaugeVec,
+    /// by drive
+    pub token_refresh_failures: IntCounterVec,
+    /// download events waiting to be written to the sinks
+    /// by sink
+    pub event_queue_depth: IntGaugeVec,
+    /// by sink
+    pub events_written: IntCounterVec,
+    /// by sink, `all` when dropped before reaching any sink, and `reason`
+    pub events_dropped: IntCounterVec,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("rlist".to_owned()), None).unwrap();
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["route", "method", "status"],
+        ).unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time to handle HTTP requests"),
+            &["route", "method", "status"],
+        ).unwrap();
+        let downloads = IntCounterVec::new(
+            Opts::new("downloads_total", "Download links given out"),
+            &["drive"],
+        ).unwrap();
+        let captcha = IntCounterVec::new(
+            Opts::new("captcha_verifications_total", "Captcha verifications"),
+            &["result"],
+        ).unwrap();
+        let refresh_duration = HistogramVec::new(
+            HistogramOpts::new("drive_refresh_duration_seconds", "Time to load the tree of drives")
+                .buckets(vec![1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0]),
+            &["drive"],
+        ).unwrap();
+        let refresh_errors = IntCounterVec::new(
+            Opts::new("drive_refresh_errors_total", "Failed loads of the tree of drives"),
+            &["drive"],
+        ).unwrap();
+        let tree_items = IntGaugeVec::new(
+            Opts::new("tree_items", "Files and directories in the tree"),
+            &["drive"],
+        ).unwrap();
+        let token_refresh_failures = IntCounterVec::new(
+            Opts::new("token_refresh_failures_total", "Failed renewals of access tokens"),
+            &["drive"],
+        ).unwrap();
+        let event_queue_depth = IntGaugeVec::new(
+            Opts::new("event_queue_depth", "Download events waiting to be written to the sinks"),
+            &["sink"],
+        ).unwrap();
+        let events_written = IntCounterVec::new(
+            Opts::new("events_written_total", "Download events written to the sinks"),
+            &["sink"],
+        ).unwrap();
+        let events_dropped = IntCounterVec::new(
+            Opts::new("events_dropped_total", "Download events dropped"),
+            &["sink", "reason"],
+        ).unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(request_duration.clone())).unwrap();
+        registry.register(Box::new(downloads.clone())).unwrap();
+        registry.register(Box::new(captcha.clone())).unwrap();
+        registry.register(Box::new(refresh_duration.clone())).unwrap();
+        registry.register(Box::new(refresh_errors.clone())).unwrap();
+        registry.register(Box::new(tree_items.clone())).unwrap();
+        registry.register(Box::new(token_refresh_failures.clone())).unwrap();
+        registry.register(Box::new(event_queue_depth.clone())).unwrap();
+        registry.register(Box::new(events_written.clone())).unwrap();
+        registry.register(Box::new(events_dropped.clone())).unwrap();
+        Metrics {
+            registry,
+            requests,
+            request_duration,
+            downloads,
+            captcha,
+            refresh_duration,
+            refresh_errors,
+            tree_items,
+            token_refresh_failures,
+            event_queue_depth,
+            events_written,
+            events_dropped,
+        }
+    }
+
+    /// Record the result of a captcha verification, and pass it through.
+    pub fn captcha_result(&self, passed: bool) -> bool {
+        self.captcha.with_label_values(&[if passed { "pass" } else { "fail" }]).inc();
+        passed
+    }
+
+    /// All the metrics in Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_render() {
+        METRICS.downloads.with_label_values(&["drive0"]).inc();
+        METRICS.captcha_result(false);
+        let text = METRICS.render();
+        assert!(text.contains("rlist_downloads_total{drive=\"drive0\"}"));
+        assert!(text.contains("rlist_captcha_verifications_total{result=\"fail\"}"));
+    }
+}
@@ -1,2 +1,14 @@
 pub mod captcha;
-pub mod drive_whell;
\ No newline at end of file
+pub mod drive_whell;
+pub mod drive_health;
+pub mod health_check;
+pub mod zip_stream;
+pub mod protection;
+pub mod signer;
+pub mod auth;
+pub mod api_key;
+pub mod metrics;
+pub mod logging;
+pub mod stats;
+pub mod webhook;
+pub mod attempt_limit;
@@ -0,0 +1,217 @@
+use std::net::IpAddr;
+use std::time::Duration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::HttpRequest;
+use globset::{GlobBuilder, GlobMatcher};
+use sha2::{Digest, Sha256};
+use crate::config_loader::config_struct::ProtectionSetting;
+use crate::service::attempt_limit::AttemptLimiter;
+use crate::service::signer::{digest_eq, Signer};
+
+const UNLOCK_COOKIE_PREFIX: &str = "rlist_unlock_";
+/// After this many wrong passwords for a rule from an ip, unlocking it is refused until the window passes.
+const MAX_FAILED_UNLOCKS: u32 = 10;
+/// After this many wrong passwords for a rule from all the ips, unlocking it is refused for everyone until the window passes.
+const MAX_FAILED_UNLOCKS_PER_RULE: u32 = 100;
+const FAILED_UNLOCK_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// The most ips whose wrong passwords are counted at once.
+const MAX_TRACKED_UNLOCKS: usize = 10000;
+
+enum PathMatcher {
+    /// exactly the directory
+    Path(String),
+    Glob(GlobMatcher),
+}
+
+impl PathMatcher {
+    fn new(path: &str) -> Result<Self, String> {
+        if path.contains(['*', '?', '[', '{']) {
+            let glob = GlobBuilder::new(path)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("Invalid glob {}: {}", path, e))?;
+            Ok(PathMatcher::Glob(glob.compile_matcher()))
+        } else {
+            Ok(PathMatcher::Path(normalize(path).to_owned()))
+        }
+    }
+
+    fn is_match(&self, path: &str) -> bool {
+        match self {
+            PathMatcher::Path(protected) => protected == path,
+            PathMatcher::Glob(glob) => glob.is_match(path),
+        }
+    }
+}
+
+struct ProtectedPath {
+    /// as configured, wrong passwords are counted by it, so that all the directories matched by a glob share one limit
+    pattern: String,
+    matcher: PathMatcher,
+    /// SHA-256 of the password, so that it is compared in constant time regardless of its length
+    password_digest: [u8; 32],
+}
+
+/// Why a directory is not unlocked.
+#[derive(Debug, PartialEq)]
+pub enum UnlockRejection {
+    /// not protected, or the password is wrong
+    WrongPassword,
+    /// too many wrong passwords recently
+    RateLimited,
+}
+
+/// # Directory Protection
+/// Directories matched by the rules can only be listed or downloaded from after being unlocked with their passwords.
+/// Unlocking a directory gives a cookie signed for that directory only, which expires after `unlock_max_age`.
+/// Nested protected directories must be unlocked one by one.
+pub struct Protection {
+    rules: Vec<ProtectedPath>,
+    signer: Signer,
+    max_age: Duration,
+    failures: AttemptLimiter,
+}
+
+impl Protection {
+    pub fn new(setting: ProtectionSetting) -> Result<Self, String> {
+        let rules = setting.rules.into_iter().map(|rule| {
+            Ok(ProtectedPath {
+                matcher: PathMatcher::new(&rule.path)?,
+                pattern: rule.path,
+                password_digest: Sha256::digest(rule.password.as_bytes()).into(),
+            })
+        }).collect::<Result<_, String>>()?;
+        Ok(Protection {
+            rules,
+            signer: Signer::new("unlock", setting.secret),
+            max_age: Duration::from_secs(setting.unlock_max_age),
+            failures: AttemptLimiter::new(MAX_FAILED_UNLOCKS, MAX_FAILED_UNLOCKS_PER_RULE, FAILED_UNLOCK_WINDOW, MAX_TRACKED_UNLOCKS),
+        })
+    }
+
+    /// Whether the directory itself is protected, `path` is like `/dir/sub_dir`.
+    pub fn is_protected(&self, path: &str) -> bool {
+        self.rule_of(path).is_some()
+    }
+
+    /// Whether `path` is in a protected directory at any depth, or is one itself.
+    pub fn in_protected(&self, path: &str) -> bool {
+        ancestors(normalize(path)).any(|dir| self.is_protected(dir))
+    }
+
+    /// Whether all the protected directories on the way to `path` (including itself) are unlocked by the cookies of the request.
+    pub fn is_unlocked(&self, path: &str, req: &HttpRequest) -> bool {
+        if self.rules.is_empty() {
+            return true;
+        }
+        ancestors(normalize(path))
+            .filter(|dir| self.is_protected(dir))
+            .all(|dir| {
+                req.cookie(&cookie_name(dir))
+                    .map(|cookie| self.signer.verify(dir, cookie.value()))
+                    .unwrap_or(false)
+            })
+    }
+
+    /// The cookie which unlocks the protected directory `path` for the client at `ip`, which must not come from a header.
+    /// In `FAILED_UNLOCK_WINDOW`, each ip can only try `MAX_FAILED_UNLOCKS` wrong passwords of a rule,
+    /// and all the ips together `MAX_FAILED_UNLOCKS_PER_RULE`.
+    pub fn unlock(&self, path: &str, password: &str, ip: IpAddr) -> Result<Cookie<'static>, UnlockRejection> {
+        let path = normalize(path);
+        let rule = self.rule_of(path).ok_or(UnlockRejection::WrongPassword)?;
+        if self.failures.is_limited(ip, &rule.pattern) {
+            return Err(UnlockRejection::RateLimited);
+        }
+        let digest: [u8; 32] = Sha256::digest(password.as_bytes()).into();
+        if !digest_eq(&digest, &rule.password_digest) {
+            self.failures.record_failure(ip, &rule.pattern);
+            return Err(UnlockRejection::WrongPassword);
+        }
+        Ok(Cookie::build(cookie_name(path), self.signer.issue(path, self.max_age))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .max_age(actix_web::cookie::time::Duration::seconds(self.max_age.as_secs() as i64))
+            .finish())
+    }
+
+    fn rule_of(&self, path: &str) -> Option<&ProtectedPath> {
+        self.rules.iter().find(|rule| rule.matcher.is_match(path))
+    }
+}
+
+/// Paths are compared without the trailing `/`.
+fn normalize(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+/// `/a/b/c` gives `/a`, `/a/b` and `/a/b/c`.
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/')
+        .map(|(index, _)| index)
+        .skip(1)
+        .chain(std::iter::once(path.len()))
+        .map(move |end| &path[..end])
+}
+
+/// Each directory has its own cookie, so that unlocking one does not log out another.
+fn cookie_name(path: &str) -> String {
+    let digest = Sha256::digest(path.as_bytes());
+    format!("{}{}", UNLOCK_COOKIE_PREFIX, &hex::encode(digest)[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use crate::config_loader::config_struct::ProtectRule;
+    use super::*;
+
+    fn protection() -> Protection {
+        Protection::new(ProtectionSetting {
+            secret: Some("secret".to_owned()),
+            unlock_max_age: 60,
+            rules: vec![
+                ProtectRule { path: "/private/".to_owned(), password: "a".to_owned() },
+                ProtectRule { path: "/*/secret".to_owned(), password: "b".to_owned() },
+            ],
+        }).unwrap()
+    }
+
+    #[test]
+    fn test_protection_unlock() {
+        let protection = protection();
+        assert!(protection.is_protected("/private"));
+        assert!(protection.is_protected("/docs/secret"));
+        assert!(!protection.is_protected("/docs/more/secret"));
+        assert!(protection.in_protected("/docs/secret/file"));
+        assert!(!protection.in_protected("/docs/file"));
+        let ip = IpAddr::from([10, 0, 0, 1]);
+        let attacker = IpAddr::from([10, 0, 0, 2]);
+        assert_eq!(protection.unlock("/private", "b", ip).unwrap_err(), UnlockRejection::WrongPassword);
+
+        let cookie = protection.unlock("/private", "a", ip).unwrap();
+        let req = TestRequest::default().cookie(cookie).to_http_request();
+        assert!(protection.is_unlocked("/private/file", &req));
+        assert!(protection.is_unlocked("/public/file", &req));
+        // nested protected directories are unlocked one by one
+        assert!(!protection.is_unlocked("/private/secret/file", &req));
+        assert!(!protection.is_unlocked("/docs/secret", &req));
+
+        // a cookie signed for another directory does not unlock it
+        let cookie = protection.unlock("/docs/secret", "b", ip).unwrap();
+        let forged = Cookie::new(cookie_name("/other/secret"), cookie.value().to_owned());
+        let req = TestRequest::default().cookie(forged).to_http_request();
+        assert!(!protection.is_unlocked("/other/secret", &req));
+
+        // wrong passwords are limited by ip and rule, the directories matched by a glob share the limit
+        for _ in 0..MAX_FAILED_UNLOCKS {
+            assert!(protection.unlock("/docs/secret", "a", attacker).is_err());
+        }
+        assert_eq!(protection.unlock("/other/secret", "b", attacker).unwrap_err(), UnlockRejection::RateLimited);
+        assert!(protection.unlock("/docs/secret", "b", ip).is_ok());
+        assert!(protection.unlock("/private", "a", attacker).is_ok());
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+/// # Signer
+/// Issues and verifies tokens signed by HMAC-SHA256, like the unlock cookies and the login sessions.
+/// A token is `{expires}.{signature}`, where `expires` is in seconds since unix epoch,
+/// and the signature covers the purpose, the subject and `expires`.
+/// The purpose tells the kinds of tokens apart, so that a token of one kind is never valid as another, even with the same secret.
+pub struct Signer {
+    purpose: &'static str,
+    key: Vec<u8>,
+}
+
+impl Signer {
+    /// `purpose` is like `session`. When `secret` is not provided, a random key is used, and all the tokens expire on restart.
+    pub fn new(purpose: &'static str, secret: Option<String>) -> Self {
+        let key = match secret {
+            Some(secret) => secret.into_bytes(),
+            None => {
+                let mut key = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                key
+            }
+        };
+        Signer { purpose, key }
+    }
+
+    pub fn issue(&self, subject: &str, max_age: Duration) -> String {
+        let expires = now() + max_age.as_secs();
+        let signature = self.mac(subject, expires).finalize().into_bytes();
+        format!("{}.{}", expires, hex::encode(signature))
+    }
+
+    /// Whether `token` is issued for `subject` and not expired.
+    pub fn verify(&self, subject: &str, token: &str) -> bool {
+        let Some((expires, signature)) = token.split_once('.') else { return false };
+        let (Ok(expires), Ok(signature)) = (expires.parse::<u64>(), hex::decode(signature)) else { return false };
+        expires > now() && self.mac(subject, expires).verify_slice(&signature).is_ok()
+    }
+
+    fn mac(&self, subject: &str, expires: u64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC can take key of any size");
+        mac.update(self.purpose.as_bytes());
+        mac.update(b":");
+        mac.update(subject.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+}
+
+/// Compare two digests in constant time, so that the time taken does not tell how many leading bytes match.
+pub fn digest_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b.iter()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signer_purpose() {
+        let session = Signer::new("session", Some("secret".to_owned()));
+        let unlock = Signer::new("unlock", Some("secret".to_owned()));
+        let token = unlock.issue("/private", Duration::from_secs(60));
+        assert!(unlock.verify("/private", &token));
+        assert!(!unlock.verify("/other", &token));
+        assert!(!session.verify("/private", &token));
+    }
+}
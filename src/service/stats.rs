@@ -0,0 +1,207 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use arc_swap::ArcSwapOption;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::time::interval;
+use tracing::warn;
+use crate::side_effects::{DAY_MILLIS as DAY, DownloadCounts, DownloadHistory};
+
+/// The report is computed again in background every this time.
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The windows of the statistics, by label and length in milliseconds. The last one is the longest.
+pub const WINDOWS: [(&str, u64); 3] = [("24h", DAY), ("7d", 7 * DAY), ("30d", 30 * DAY)];
+
+#[derive(Serialize)]
+pub struct PathCount {
+    pub path: String,
+    pub downloads: u64,
+}
+
+#[derive(Serialize)]
+pub struct DayCount {
+    /// like `2024-01-31`, in UTC
+    pub day: String,
+    pub downloads: u64,
+}
+
+#[derive(Serialize)]
+pub struct DriveShare {
+    pub drive: String,
+    pub downloads: u64,
+    /// of all the downloads with a drive, from 0 to 1
+    pub share: f64,
+}
+
+/// # Stats Report
+/// The downloads in the last 30 days, only the successful ones are counted.
+pub struct StatsReport {
+    /// in milliseconds since unix epoch
+    pub generated_at: u64,
+    /// the downloads of each path, in each of `WINDOWS`
+    counts: HashMap<String, Vec<u64>>,
+    /// the oldest first, including the days without downloads
+    pub per_day: Vec<DayCount>,
+    /// the most downloaded first
+    pub per_drive: Vec<DriveShare>,
+}
+
+impl StatsReport {
+    /// The starts of `WINDOWS` at `now`, to count the downloads from.
+    pub fn starts(now: u64) -> Vec<u64> {
+        WINDOWS.iter().map(|(_, length)| now.saturating_sub(*length)).collect()
+    }
+
+    /// `counts` are counted from `starts(now)`.
+    pub fn new(counts: DownloadCounts, now: u64) -> Self {
+        let mut per_day: BTreeMap<u64, u64> = BTreeMap::new();
+        let (_, longest) = WINDOWS[WINDOWS.len() - 1];
+        for day in 0..longest / DAY {
+            per_day.insert(now.saturating_sub(day * DAY) / DAY, 0);
+        }
+        for (day, downloads) in counts.per_day {
+            if let Some(count) = per_day.get_mut(&day) {
+                *count += downloads;
+            }
+        }
+        let with_drive: u64 = counts.per_drive.values().sum();
+        let mut per_drive: Vec<DriveShare> = counts.per_drive.into_iter()
+            .map(|(drive, downloads)| DriveShare {
+                drive,
+                downloads,
+                share: downloads as f64 / with_drive as f64,
+            })
+            .collect();
+        per_drive.sort_by(|a, b| b.downloads.cmp(&a.downloads).then_with(|| a.drive.cmp(&b.drive)));
+        StatsReport {
+            generated_at: now,
+            counts: counts.per_path,
+            per_day: per_day.into_iter().map(|(day, downloads)| DayCount { day: day_of(day * DAY), downloads }).collect(),
+            per_drive,
+        }
+    }
+
+    /// The most downloaded paths in the `window`-th of `WINDOWS`, only the ones where `can_list(path)` is true.
+    pub fn top_files(&self, window: usize, limit: usize, can_list: &dyn Fn(&str) -> bool) -> Vec<PathCount> {
+        let mut top: Vec<PathCount> = self.counts.iter()
+            .map(|(path, counts)| (path, counts[window]))
+            .filter(|(path, downloads)| *downloads > 0 && can_list(path))
+            .map(|(path, downloads)| PathCount { path: path.clone(), downloads })
+            .collect();
+        top.sort_by(|a, b| b.downloads.cmp(&a.downloads).then_with(|| a.path.cmp(&b.path)));
+        top.truncate(limit);
+        top
+    }
+
+    /// The downloads of `path` in the `window`-th of `WINDOWS`.
+    pub fn downloads(&self, window: usize, path: &str) -> u64 {
+        self.counts.get(path).map(|counts| counts[window]).unwrap_or(0)
+    }
+}
+
+fn day_of(timestamp: u64) -> String {
+    DateTime::<Utc>::from(UNIX_EPOCH + Duration::from_millis(timestamp)).format("%Y-%m-%d").to_string()
+}
+
+/// # Stats
+/// Computes the statistics from the download log of a local sink in background, every `STATS_INTERVAL`.
+/// Requests only read the last report, so they never wait for the download log.
+pub struct Stats {
+    enabled: bool,
+    latest: Arc<ArcSwapOption<StatsReport>>,
+}
+
+impl Stats {
+    /// The first report is computed right away, if there is a download log.
+    pub fn new(history: Option<Arc<dyn DownloadHistory>>) -> Self {
+        let latest = Arc::new(ArcSwapOption::empty());
+        let enabled = history.is_some();
+        if let Some(history) = history {
+            let latest = latest.clone();
+            tokio::spawn(async move {
+                let mut interval = interval(STATS_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    // the last report is kept when it fails
+                    match compute(history.as_ref()).await {
+                        Ok(report) => latest.store(Some(Arc::new(report))),
+                        Err(e) => warn!("Failed to compute stats: {}", e),
+                    }
+                }
+            });
+        }
+        Stats { enabled, latest }
+    }
+
+    /// Whether there is a download log to compute from.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The last computed report, `None` until the first one is done or if there is no download log.
+    pub fn report(&self) -> Option<Arc<StatsReport>> {
+        self.latest.load_full()
+    }
+}
+
+async fn compute(history: &dyn DownloadHistory) -> Result<StatsReport, String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let counts = history.count_since(&StatsReport::starts(now)).await?;
+    Ok(StatsReport::new(counts, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::side_effects::{DownloadKind, DownloadRecord};
+    use super::*;
+
+    fn record(path: &str, drive: &str, age: u64, status: u16) -> DownloadRecord {
+        DownloadRecord {
+            timestamp: 100 * DAY - age,
+            path: path.to_owned(),
+            kind: DownloadKind::File,
+            ip: String::new(),
+            user_agent: String::new(),
+            api_key: None,
+            drive: Some(drive.to_owned()),
+            mirror: None,
+            file_size: None,
+            status,
+            referer: None,
+        }
+    }
+
+    #[test]
+    fn test_stats_windows() {
+        let mut archive = record("/", "d1", 1000, 200);
+        archive.kind = DownloadKind::Archive;
+        let records = [
+            archive,
+            record("/a", "d1", 1000, 307),
+            record("/a", "d2", 2 * DAY, 307),
+            record("/b", "d1", 3 * DAY, 307),
+            record("/b", "d1", 3 * DAY, 307),
+            record("/b", "d1", 1000, 403),
+            record("/c", "d1", 40 * DAY, 307),
+        ];
+        let mut counts = DownloadCounts::default();
+        let starts = StatsReport::starts(100 * DAY);
+        records.iter().for_each(|record| counts.add(record, &starts));
+        let report = StatsReport::new(counts, 100 * DAY);
+        let top = report.top_files(0, 10, &|_| true);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].path, "/a");
+        // ties are sorted by path
+        assert_eq!(report.top_files(1, 10, &|_| true)[0].path, "/a");
+        assert_eq!(report.downloads(1, "/b"), 2);
+        assert!(report.top_files(1, 10, &|path| path != "/b").iter().all(|count| count.path != "/b"));
+        assert_eq!(report.downloads(2, "/a"), 2);
+        assert_eq!(report.downloads(2, "/c"), 0);
+        assert_eq!(report.per_day.len(), 30);
+        assert_eq!(report.per_day.iter().map(|day| day.downloads).sum::<u64>(), 4);
+        assert_eq!(report.per_drive[0].drive, "d1");
+        assert_eq!(report.per_drive[0].share, 0.75);
+    }
+}
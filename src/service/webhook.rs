@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::warn;
+use uuid::Uuid;
+use crate::config_loader::config_struct::{WebhookConfig, WebhookEventKind};
+use crate::side_effects::DownloadKind;
+use crate::vfs::diff::ChangedFile;
+
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An event posted to the webhooks, as `{"id", "timestamp", "event", ...fields}`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Download {
+        path: String,
+        kind: DownloadKind,
+        ip: String,
+        drive: Option<String>,
+        file_size: Option<u64>,
+        status: u16,
+    },
+    DriveUnhealthy {
+        drive: String,
+        reason: String,
+    },
+    RefreshFailed {
+        drive: String,
+        error: String,
+    },
+    NewFiles {
+        generation: u64,
+        files: Vec<ChangedFile>,
+    },
+}
+
+impl WebhookEvent {
+    fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::Download { .. } => WebhookEventKind::Download,
+            WebhookEvent::DriveUnhealthy { .. } => WebhookEventKind::DriveUnhealthy,
+            WebhookEvent::RefreshFailed { .. } => WebhookEventKind::RefreshFailed,
+            WebhookEvent::NewFiles { .. } => WebhookEventKind::NewFiles,
+        }
+    }
+
+    /// The event as seen by a webhook watching `paths`, `None` if nothing of it is under them.
+    fn under(&self, paths: &[String]) -> Option<WebhookEvent> {
+        let watched = |path: &str| paths.is_empty() || paths.iter().any(|prefix| {
+            let prefix = prefix.trim_end_matches('/');
+            path == prefix || path.starts_with(&format!("{}/", prefix))
+        });
+        match self {
+            WebhookEvent::Download { path, .. } => watched(path).then(|| self.clone()),
+            WebhookEvent::NewFiles { generation, files } => {
+                let files: Vec<ChangedFile> = files.iter().filter(|file| watched(&file.path)).cloned().collect();
+                (!files.is_empty()).then_some(WebhookEvent::NewFiles { generation: *generation, files })
+            }
+            _ => Some(self.clone()),
+        }
+    }
+}
+
+fn event_name(kind: WebhookEventKind) -> &'static str {
+    match kind {
+        WebhookEventKind::Download => "download",
+        WebhookEventKind::DriveUnhealthy => "drive_unhealthy",
+        WebhookEventKind::RefreshFailed => "refresh_failed",
+        WebhookEventKind::NewFiles => "new_files",
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    /// the same as `X-Rlist-Delivery`, kept across retries so that receivers can deduplicate
+    id: String,
+    /// in milliseconds since unix epoch
+    timestamp: u128,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+/// `sha256={hex}` of the HMAC-SHA256 of `body` by `secret`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// # Webhooks
+/// Posts the subscribed events to each webhook as JSON in background, retried with exponential backoff.
+/// Each delivery has `X-Rlist-Event`, `X-Rlist-Delivery`, and `X-Rlist-Signature` when the webhook has a secret.
+pub struct Webhooks {
+    hooks: Vec<Arc<WebhookConfig>>,
+    client: reqwest::Client,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<WebhookConfig>) -> Self {
+        Webhooks {
+            hooks: hooks.into_iter().map(Arc::new).collect(),
+            client: reqwest::Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .build()
+                .expect("Failed to create http client"),
+        }
+    }
+
+    pub fn notify(&self, event: WebhookEvent) {
+        let kind = event.kind();
+        for hook in self.hooks.iter().filter(|hook| hook.events.contains(&kind)) {
+            let Some(event) = event.under(&hook.paths) else { continue };
+            let hook = hook.clone();
+            let client = self.client.clone();
+            tokio::spawn(async move { deliver(&client, &hook, &event).await });
+        }
+    }
+}
+
+async fn deliver(client: &reqwest::Client, hook: &WebhookConfig, event: &WebhookEvent) {
+    let id = Uuid::new_v4().to_string();
+    let payload = Payload {
+        id: id.clone(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis(),
+        event,
+    };
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(e) => return warn!("Failed to serialize webhook event: {}", e),
+    };
+    let mut attempt = 0;
+    loop {
+        let mut request = client.post(&hook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Rlist-Event", event_name(event.kind()))
+            .header("X-Rlist-Delivery", &id);
+        if let Some(secret) = &hook.secret {
+            request = request.header("X-Rlist-Signature", sign(secret, &body));
+        }
+        let result = request.body(body.clone()).send().await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("Failed to post webhook to {}: {}", hook.url, e));
+        match result {
+            Ok(_) => return,
+            Err(e) if attempt < hook.max_retries => {
+                let backoff = (BASE_BACKOFF * 2u32.pow(attempt)).min(MAX_BACKOFF);
+                warn!("{}, retry in {:?}", e, backoff);
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+            Err(e) => return warn!("{}, dropped after {} retries", e, hook.max_retries),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_signature_and_paths() {
+        // from the test vectors of RFC 4231
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        );
+        let file = |path: &str| ChangedFile { path: path.to_owned(), size: 0, last_modified: 0 };
+        let event = WebhookEvent::NewFiles { generation: 2, files: vec![file("/releases/v1.zip"), file("/other/a")] };
+        let Some(WebhookEvent::NewFiles { files, .. }) = event.under(&["/releases/".to_owned()]) else { panic!() };
+        assert_eq!(files.len(), 1);
+        assert!(event.under(&["/docs".to_owned()]).is_none());
+        let payload = serde_json::to_value(Payload { id: "x".to_owned(), timestamp: 0, event: &event }).unwrap();
+        assert_eq!(payload["event"], "new_files");
+        assert_eq!(payload["files"][0]["path"], "/releases/v1.zip");
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::SystemTime;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+const DATA_DESCRIPTOR: u32 = 0x08074b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x06064b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR: u32 = 0x07064b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+/// ZIP64 is needed to extract
+const VERSION: u16 = 45;
+/// bit 3: sizes and crc are in the data descriptor, bit 11: names are utf-8
+const FLAGS: u16 = 0x0808;
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+
+/// # Streaming ZIP Writer
+/// Writes a ZIP64 archive in store mode (no compression) as a stream, without knowing the crc and sizes of the files in advance.
+/// It only produces the headers, the content of each file should be written between `start_entry` and `finish_entry`.
+///
+/// Every entry is written in ZIP64, so that neither the file sizes nor the archive size are limited to 4 GiB.
+pub struct ZipStreamWriter {
+    offset: u64,
+    entries: Vec<CentralEntry>,
+    current: Option<CentralEntry>,
+}
+
+struct CentralEntry {
+    name: String,
+    /// checked when the entry is started
+    name_len: u16,
+    dos_time: u16,
+    dos_date: u16,
+    crc: u32,
+    size: u64,
+    offset: u64,
+}
+
+impl ZipStreamWriter {
+    pub fn new() -> Self {
+        ZipStreamWriter {
+            offset: 0,
+            entries: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// The local file header of a new entry. Fails if the name can not be stored, see `check_name`.
+    pub fn start_entry(&mut self, name: &str, last_modified: SystemTime) -> Result<Vec<u8>, String> {
+        let name_len = check_name(name)?;
+        let (dos_time, dos_date) = dos_date_time(last_modified);
+        let mut header = Vec::with_capacity(30 + name.len() + 20);
+        put_u32(&mut header, LOCAL_FILE_HEADER);
+        put_u16(&mut header, VERSION);
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, 0);            // store
+        put_u16(&mut header, dos_time);
+        put_u16(&mut header, dos_date);
+        put_u32(&mut header, 0);            // crc, in data descriptor
+        put_u32(&mut header, u32::MAX);     // compressed size, in zip64 extra field
+        put_u32(&mut header, u32::MAX);     // uncompressed size, in zip64 extra field
+        put_u16(&mut header, name_len);
+        put_u16(&mut header, 20);
+        header.extend_from_slice(name.as_bytes());
+        put_u16(&mut header, ZIP64_EXTRA_FIELD);
+        put_u16(&mut header, 16);
+        put_u64(&mut header, 0);            // sizes are unknown yet, in data descriptor
+        put_u64(&mut header, 0);
+        self.current = Some(CentralEntry {
+            name: name.to_owned(),
+            name_len,
+            dos_time,
+            dos_date,
+            crc: 0,
+            size: 0,
+            offset: self.offset,
+        });
+        self.offset += header.len() as u64;
+        Ok(header)
+    }
+
+    /// The data descriptor of the current entry, after `size` bytes of content with `crc` are written.
+    pub fn finish_entry(&mut self, crc: u32, size: u64) -> Vec<u8> {
+        let mut entry = self.current.take().expect("No entry is started");
+        entry.crc = crc;
+        entry.size = size;
+        let mut descriptor = Vec::with_capacity(24);
+        put_u32(&mut descriptor, DATA_DESCRIPTOR);
+        put_u32(&mut descriptor, crc);
+        put_u64(&mut descriptor, size);
+        put_u64(&mut descriptor, size);
+        self.offset += size + descriptor.len() as u64;
+        self.entries.push(entry);
+        descriptor
+    }
+
+    /// The central directory and the end records, which close the archive.
+    pub fn finish(self) -> Vec<u8> {
+        let mut tail = Vec::new();
+        let central_directory_offset = self.offset;
+        for entry in self.entries.iter() {
+            put_u32(&mut tail, CENTRAL_DIRECTORY_HEADER);
+            put_u16(&mut tail, VERSION);        // made by
+            put_u16(&mut tail, VERSION);        // needed to extract
+            put_u16(&mut tail, FLAGS);
+            put_u16(&mut tail, 0);              // store
+            put_u16(&mut tail, entry.dos_time);
+            put_u16(&mut tail, entry.dos_date);
+            put_u32(&mut tail, entry.crc);
+            put_u32(&mut tail, u32::MAX);       // sizes and offset, in zip64 extra field
+            put_u32(&mut tail, u32::MAX);
+            put_u16(&mut tail, entry.name_len);
+            put_u16(&mut tail, 28);
+            put_u16(&mut tail, 0);              // comment length
+            put_u16(&mut tail, 0);              // disk number
+            put_u16(&mut tail, 0);              // internal attributes
+            put_u32(&mut tail, 0);              // external attributes
+            put_u32(&mut tail, u32::MAX);
+            tail.extend_from_slice(entry.name.as_bytes());
+            put_u16(&mut tail, ZIP64_EXTRA_FIELD);
+            put_u16(&mut tail, 24);
+            put_u64(&mut tail, entry.size);
+            put_u64(&mut tail, entry.size);
+            put_u64(&mut tail, entry.offset);
+        }
+        let central_directory_size = tail.len() as u64;
+        let zip64_end_offset = central_directory_offset + central_directory_size;
+        let count = self.entries.len() as u64;
+
+        put_u32(&mut tail, ZIP64_END_OF_CENTRAL_DIRECTORY);
+        put_u64(&mut tail, 44);                 // size of the rest of this record
+        put_u16(&mut tail, VERSION);
+        put_u16(&mut tail, VERSION);
+        put_u32(&mut tail, 0);                  // this disk
+        put_u32(&mut tail, 0);                  // disk of central directory
+        put_u64(&mut tail, count);
+        put_u64(&mut tail, count);
+        put_u64(&mut tail, central_directory_size);
+        put_u64(&mut tail, central_directory_offset);
+
+        put_u32(&mut tail, ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR);
+        put_u32(&mut tail, 0);
+        put_u64(&mut tail, zip64_end_offset);
+        put_u32(&mut tail, 1);                  // total disks
+
+        put_u32(&mut tail, END_OF_CENTRAL_DIRECTORY);
+        put_u16(&mut tail, 0);
+        put_u16(&mut tail, 0);
+        put_u16(&mut tail, u16::MAX);           // the real values are in zip64 end of central directory
+        put_u16(&mut tail, u16::MAX);
+        put_u32(&mut tail, u32::MAX);
+        put_u32(&mut tail, u32::MAX);
+        put_u16(&mut tail, 0);                  // comment length
+        tail
+    }
+}
+
+/// The length of `name` in the headers. Fails if it is longer than 65535 bytes, which ZIP can not store.
+pub fn check_name(name: &str) -> Result<u16, String> {
+    u16::try_from(name.len()).map_err(|_| format!("A name of {} bytes is too long for ZIP", name.len()))
+}
+
+/// MS-DOS time and date, which can not be earlier than 1980.
+fn dos_date_time(time: SystemTime) -> (u16, u16) {
+    let time = DateTime::<Utc>::from(time);
+    if time.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let dos_time = (time.hour() << 11) | (time.minute() << 5) | (time.second() / 2);
+    let dos_date = (((time.year() - 1980) as u32) << 9) | (time.month() << 5) | time.day();
+    (dos_time as u16, dos_date as u16)
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zip_stream_offsets() {
+        let content = b"hello";
+        let mut writer = ZipStreamWriter::new();
+        assert!(writer.start_entry(&"a".repeat(65536), SystemTime::now()).is_err());
+        let mut archive = writer.start_entry("dir/hello.txt", SystemTime::now()).unwrap();
+        archive.extend_from_slice(content);
+        archive.extend(writer.finish_entry(crc32fast::hash(content), content.len() as u64));
+        archive.extend(writer.finish());
+
+        // the end of central directory record is the last 22 bytes
+        let end = &archive[archive.len() - 22..];
+        assert_eq!(&end[..4], &END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        // the locator points to the zip64 end of central directory record
+        let locator = &archive[archive.len() - 42..archive.len() - 22];
+        assert_eq!(&locator[..4], &ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR.to_le_bytes());
+        let zip64_end = u64::from_le_bytes(locator[8..16].try_into().unwrap()) as usize;
+        assert_eq!(&archive[zip64_end..zip64_end + 4], &ZIP64_END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        // which points to the central directory
+        let central = u64::from_le_bytes(archive[zip64_end + 48..zip64_end + 56].try_into().unwrap()) as usize;
+        assert_eq!(&archive[central..central + 4], &CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+        assert_eq!(u32::from_le_bytes(archive[central + 16..central + 20].try_into().unwrap()), crc32fast::hash(content));
+    }
+}
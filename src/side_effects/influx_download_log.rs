@@ -1,67 +1,165 @@
-use std::pin::Pin;
-use std::sync::Arc;
-use influxdb::{Client, WriteQuery, Timestamp};
-use crate::config_loader::config_struct::InfluxConfig;
+use std::time::{Duration, UNIX_EPOCH};
+use crate::config_loader::config_struct::{InfluxConfig, InfluxPrecision};
+use crate::side_effects::line_protocol::{FieldValue, Point};
 use crate::side_effects::{SideEffect, SideEffectProps};
 
-type WriteFunction = Arc<dyn Fn(SideEffectProps) -> Pin<Box<dyn std::future::Future<Output=()> + Send>> + Send + Sync>;
+/// A write which takes longer is failed and retried, so that a hung server does not stall the sink.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
 
-pub struct LogEffect {
-    write_fn: WriteFunction
+/// How the points are written, by the version of InfluxDB.
+enum InfluxApi {
+    V1 {
+        database: String,
+        auth: Option<(String, String)>,
+    },
+    V2 {
+        org: String,
+        bucket: String,
+        token: String,
+    },
 }
 
-async fn log_with_influx(props: SideEffectProps, client: Arc<Client>) {
-    let user_ip = props.request_ip;
-    let user_agent = props.user_agent;
-    let file_name = props.file_name;
-    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-    let write_query = WriteQuery::new(Timestamp::Seconds(now as u128), "download_log")
-        .add_field("user_ip", user_ip)
-        .add_field("user_agent", user_agent)
-        .add_field("file_name", file_name);
-    match client.query(&write_query).await {
-        _ => {}
-    };
+/// # Influx Download Log
+/// Writes the downloads into the `download_log` measurement of InfluxDB, a batch per request in line protocol.
+/// The kind, the drive, the mirror, the API key and the status are tags, as they have few distinct values, the others are fields.
+pub struct InfluxLog {
+    url: String,
+    api: InfluxApi,
+    precision: InfluxPrecision,
+    client: reqwest::Client,
 }
 
-fn connect_to_influx(config: InfluxConfig) -> Client {
-    let InfluxConfig { url, database, username, password } = config;
-    match (username, password) {
-        (Some(username), Some(password)) => {
-            Client::new(url, database)
-                .with_auth(username, password)
-        }
-        _ => {
-            Client::new(url, database)
+impl InfluxLog {
+    /// Fails if neither `bucket` nor `database` is given, or `bucket` is given without `org` and `token`.
+    pub fn new(config: InfluxConfig) -> Result<Self, String> {
+        let api = match (config.bucket, config.database) {
+            (Some(bucket), _) => InfluxApi::V2 {
+                org: config.org.ok_or("`org` is required with `bucket`")?,
+                token: config.token.ok_or("`token` is required with `bucket`")?,
+                bucket,
+            },
+            (None, Some(database)) => InfluxApi::V1 {
+                database,
+                auth: config.username.zip(config.password),
+            },
+            (None, None) => return Err("Either `bucket` or `database` is required".to_owned()),
+        };
+        Ok(InfluxLog {
+            url: config.url.trim_end_matches('/').to_owned(),
+            api,
+            precision: config.precision,
+            client: reqwest::Client::builder()
+                .timeout(WRITE_TIMEOUT)
+                .build()
+                .map_err(|e| format!("Failed to create http client: {}", e))?,
+        })
+    }
+
+    fn write_request(&self) -> reqwest::RequestBuilder {
+        match &self.api {
+            InfluxApi::V1 { database, auth } => {
+                // 1.x calls microseconds and nanoseconds `u` and `n`
+                let precision = match self.precision {
+                    InfluxPrecision::Seconds => "s",
+                    InfluxPrecision::Milliseconds => "ms",
+                    InfluxPrecision::Microseconds => "u",
+                    InfluxPrecision::Nanoseconds => "n",
+                };
+                let request = self.client.post(format!("{}/write", self.url))
+                    .query(&[("db", database.as_str()), ("precision", precision)]);
+                match auth {
+                    Some((username, password)) => request.basic_auth(username, Some(password)),
+                    None => request,
+                }
+            }
+            InfluxApi::V2 { org, bucket, token } => {
+                let precision = match self.precision {
+                    InfluxPrecision::Seconds => "s",
+                    InfluxPrecision::Milliseconds => "ms",
+                    InfluxPrecision::Microseconds => "us",
+                    InfluxPrecision::Nanoseconds => "ns",
+                };
+                self.client.post(format!("{}/api/v2/write", self.url))
+                    .query(&[("org", org.as_str()), ("bucket", bucket.as_str()), ("precision", precision)])
+                    .header("Authorization", format!("Token {}", token))
+            }
         }
     }
 }
 
+fn to_point(props: &SideEffectProps, precision: InfluxPrecision) -> Point {
+    let since_epoch = props.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let time = match precision {
+        InfluxPrecision::Seconds => since_epoch.as_secs() as u128,
+        InfluxPrecision::Milliseconds => since_epoch.as_millis(),
+        InfluxPrecision::Microseconds => since_epoch.as_micros(),
+        InfluxPrecision::Nanoseconds => since_epoch.as_nanos(),
+    };
+    let mut point = Point::new("download_log", time)
+        .tag("status", props.status.to_string())
+        .tag("kind", props.kind.as_str().to_owned());
+    if let Some(drive) = &props.drive {
+        point = point.tag("drive", drive.clone());
+    }
+    if let Some(mirror) = &props.mirror {
+        point = point.tag("mirror", mirror.clone());
+    }
+    if let Some(api_key) = &props.api_key {
+        point = point.tag("api_key", api_key.clone());
+    }
+    point = point
+        .field("user_ip", FieldValue::String(props.request_ip.clone()))
+        .field("user_agent", FieldValue::String(props.user_agent.clone()))
+        .field("file_name", FieldValue::String(props.file_name.clone()));
+    if let Some(file_size) = props.file_size {
+        point = point.field("file_size", FieldValue::Integer(file_size as i64));
+    }
+    if let Some(referer) = &props.referer {
+        point = point.field("referer", FieldValue::String(referer.clone()));
+    }
+    point
+}
+
 #[async_trait::async_trait]
-impl SideEffect<Option<InfluxConfig>> for LogEffect {
-    fn new(config: Option<InfluxConfig>) -> Self {
-        let client = match config {
-            Some(config) => Some(Arc::new(connect_to_influx(config))),
-            None => None,
-        };
-        let write_fn: WriteFunction = if client.is_none() {
-            Arc::new(|_| Box::pin(async {}))
-        } else {
-            Arc::new(move|props| {
-                let client_clone = client.clone().unwrap();
-                Box::pin(
-                    async move {
-                        log_with_influx(props, client_clone.clone()).await;
-                    }
-                )
-            })
-        };
-        LogEffect {
-            write_fn,
-        }
+impl SideEffect for InfluxLog {
+    fn name(&self) -> &str {
+        "influx"
     }
 
-    async fn do_effect(&self, props: SideEffectProps) {
-        (self.write_fn)(props).await;
+    async fn do_effects(&self, batch: &[SideEffectProps]) -> Result<(), String> {
+        let body: Vec<String> = batch.iter().map(|props| to_point(props, self.precision).to_line()).collect();
+        self.write_request()
+            .body(body.join("\n"))
+            .send().await
+            .and_then(|res| res.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("Failed to write to influxdb: {}", e))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+    use super::*;
+
+    #[test]
+    fn test_influx_point_tags_and_fields() {
+        let props = SideEffectProps {
+            request_ip: "1.2.3.4".to_owned(),
+            user_agent: "curl".to_owned(),
+            file_name: "/a.zip".to_owned(),
+            kind: crate::side_effects::DownloadKind::File,
+            api_key: None,
+            drive: Some("drive0".to_owned()),
+            mirror: None,
+            file_size: Some(42),
+            status: 307,
+            referer: None,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+        };
+        assert_eq!(
+            to_point(&props, InfluxPrecision::Milliseconds).to_line(),
+            r#"download_log,status=307,kind=file,drive=drive0 user_ip="1.2.3.4",user_agent="curl",file_name="/a.zip",file_size=42i 1700000000123"#,
+        );
+    }
+}
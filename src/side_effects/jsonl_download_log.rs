@@ -0,0 +1,202 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use chrono::{DateTime, NaiveDate, Utc};
+use crate::config_loader::config_struct::{JsonlSinkConfig, RotatePolicy};
+use crate::side_effects::{DownloadCounts, DownloadHistory, DownloadRecord, SideEffect, SideEffectProps};
+
+/// # JSON Lines Download Log
+/// Appends each download as a `DownloadRecord` in a line of JSON, to a file rotated by day or by size.
+/// Rotated files are renamed with the time of rotation, and only the newest `retention` of them are kept.
+pub struct JsonlLog {
+    // the file is written in a blocking task, so it is shared by `Arc`
+    file: Arc<Mutex<JsonlFile>>,
+}
+
+struct JsonlFile {
+    path: PathBuf,
+    rotate: RotatePolicy,
+    max_size: u64,
+    retention: usize,
+    file: File,
+    size: u64,
+    /// the day the file was started, for daily rotation
+    day: NaiveDate,
+}
+
+impl JsonlLog {
+    /// Fails if the file can not be opened.
+    pub fn new(config: JsonlSinkConfig) -> Result<Self, String> {
+        let path = PathBuf::from(config.path);
+        let (file, size) = open_append(&path)?;
+        // an existing file was started on the day it was last written
+        let day = file.metadata().and_then(|meta| meta.modified())
+            .map(|time| DateTime::<Utc>::from(time).date_naive())
+            .unwrap_or_else(|_| Utc::now().date_naive());
+        Ok(JsonlLog {
+            file: Arc::new(Mutex::new(JsonlFile {
+                path,
+                rotate: config.rotate,
+                max_size: config.max_size,
+                retention: config.retention,
+                file,
+                size,
+                day,
+            })),
+        })
+    }
+}
+
+fn open_append(path: &Path) -> Result<(File, u64), String> {
+    let file = OpenOptions::new().create(true).append(true).open(path)
+        .map_err(|e| format!("Can not open {}: {}", path.display(), e))?;
+    let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+    Ok((file, size))
+}
+
+impl JsonlFile {
+    fn append(&mut self, lines: &[u8]) -> Result<(), String> {
+        let today = Utc::now().date_naive();
+        let needs_rotation = match self.rotate {
+            RotatePolicy::Daily => self.day != today,
+            RotatePolicy::Size => self.size > 0 && self.size + lines.len() as u64 > self.max_size,
+        };
+        if needs_rotation {
+            self.rotate_file()?;
+            self.day = today;
+        }
+        self.file.write_all(lines)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        self.size += lines.len() as u64;
+        Ok(())
+    }
+
+    /// Rename the current file with the time, start a new one, and delete the oldest rotated files.
+    fn rotate_file(&mut self) -> Result<(), String> {
+        let rotated = format!("{}.{}", self.path.display(), Utc::now().format("%Y%m%dT%H%M%S%.3f"));
+        std::fs::rename(&self.path, &rotated)
+            .map_err(|e| format!("Failed to rotate {}: {}", self.path.display(), e))?;
+        let (file, size) = open_append(&self.path)?;
+        self.file = file;
+        self.size = size;
+        self.remove_expired();
+        Ok(())
+    }
+
+    /// Keep the newest `retention` rotated files.
+    fn remove_expired(&self) {
+        let rotated = rotated_files(&self.path);
+        let expired = rotated.len().saturating_sub(self.retention);
+        for path in &rotated[..expired] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// The rotated files of `path`, the oldest first, as the names sort by the time of rotation.
+fn rotated_files(path: &Path) -> Vec<PathBuf> {
+    let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else { return Vec::new() };
+    let prefix = format!("{}.", file_name);
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+    let mut rotated: Vec<PathBuf> = entries.filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.starts_with(&prefix)))
+        .map(|entry| entry.path())
+        .collect();
+    rotated.sort();
+    rotated
+}
+
+/// Count the records of all the files line by line, skipping the files last written before the earliest start
+/// and the broken lines, including the ones which are not UTF-8. A file is only read up to an I/O error.
+fn count_since(path: &Path, starts: &[u64]) -> DownloadCounts {
+    let since_time = UNIX_EPOCH + Duration::from_millis(starts.iter().copied().min().unwrap_or(0));
+    let mut files = rotated_files(path);
+    files.push(path.to_owned());
+    let mut counts = DownloadCounts::default();
+    for path in files {
+        let Ok(file) = File::open(&path) else { continue };
+        if file.metadata().and_then(|meta| meta.modified()).is_ok_and(|modified| modified < since_time) {
+            continue;
+        }
+        BufReader::new(file).split(b'\n')
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_slice::<DownloadRecord>(&line).ok())
+            .for_each(|record| counts.add(&record, starts));
+    }
+    counts
+}
+
+#[async_trait::async_trait]
+impl DownloadHistory for JsonlLog {
+    async fn count_since(&self, starts: &[u64]) -> Result<DownloadCounts, String> {
+        let path = self.file.lock().unwrap().path.clone();
+        let starts = starts.to_vec();
+        tokio::task::spawn_blocking(move || count_since(&path, &starts))
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl SideEffect for JsonlLog {
+    fn name(&self) -> &str {
+        "jsonl"
+    }
+
+    async fn do_effects(&self, batch: &[SideEffectProps]) -> Result<(), String> {
+        let mut lines = Vec::new();
+        for props in batch {
+            serde_json::to_writer(&mut lines, &DownloadRecord::from(props)).map_err(|e| e.to_string())?;
+            lines.push(b'\n');
+        }
+        let file = self.file.clone();
+        tokio::task::spawn_blocking(move || file.lock().unwrap().append(&lines))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonl_rotate_by_size() {
+        let dir = std::env::temp_dir().join(format!("rlist-jsonl-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("downloads.jsonl");
+        let log = JsonlLog::new(JsonlSinkConfig {
+            path: path.to_str().unwrap().to_owned(),
+            rotate: RotatePolicy::Size,
+            max_size: 10,
+            retention: 1,
+        }).unwrap();
+        let mut file = log.file.lock().unwrap();
+        for _ in 0..3 {
+            file.append(b"{\"a\":123}\n").unwrap();
+            // rotated files are named by milliseconds
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        drop(file);
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        // the current file and a rotated one, the other rotated one is expired
+        assert_eq!(files, 2);
+    }
+
+    #[test]
+    fn test_jsonl_skips_broken_lines() {
+        let path = std::env::temp_dir().join(format!("rlist-jsonl-{}.jsonl", uuid::Uuid::new_v4()));
+        let record = br#"{"timestamp":5,"path":"/a","ip":"","user_agent":"","api_key":null,"drive":null,"mirror":null,"file_size":null,"status":200,"referer":null}"#;
+        let mut content = record.to_vec();
+        content.extend_from_slice(b"\n\xff\xfe not utf-8\n{\"broken\n");
+        content.extend_from_slice(record);
+        std::fs::write(&path, content).unwrap();
+        let counts = count_since(&path, &[0]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(counts.per_path["/a"], vec![2]);
+    }
+}
@@ -0,0 +1,95 @@
+/// # Line Protocol Point
+/// A point of InfluxDB line protocol, like `download_log,drive=a file_name="b",status=200i 1700000000`.
+/// Tags are indexed and should have few distinct values, the others should be fields.
+pub struct Point {
+    measurement: String,
+    tags: Vec<(String, String)>,
+    fields: Vec<(String, FieldValue)>,
+    timestamp: u128,
+}
+
+pub enum FieldValue {
+    String(String),
+    /// written with the `i` suffix, which all the versions of InfluxDB take, unlike `u` for unsigned integers
+    Integer(i64),
+}
+
+impl Point {
+    /// `timestamp` is in the precision of the write request.
+    pub fn new(measurement: &str, timestamp: u128) -> Self {
+        Point {
+            measurement: measurement.to_owned(),
+            tags: Vec::new(),
+            fields: Vec::new(),
+            timestamp,
+        }
+    }
+
+    pub fn tag(mut self, key: &str, value: impl Into<String>) -> Self {
+        let value = value.into();
+        // empty tag values are not allowed
+        if !value.is_empty() {
+            self.tags.push((key.to_owned(), value));
+        }
+        self
+    }
+
+    pub fn field(mut self, key: &str, value: FieldValue) -> Self {
+        self.fields.push((key.to_owned(), value));
+        self
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut line = escape(&self.measurement, &[',', ' ']);
+        for (key, value) in &self.tags {
+            line.push_str(&format!(",{}={}", escape(key, &[',', '=', ' ']), escape(value, &[',', '=', ' '])));
+        }
+        let fields: Vec<String> = self.fields.iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    FieldValue::String(value) => format!("\"{}\"", escape(value, &['"'])),
+                    FieldValue::Integer(value) => format!("{}i", value),
+                };
+                format!("{}={}", escape(key, &[',', '=', ' ']), value)
+            })
+            .collect();
+        line.push(' ');
+        line.push_str(&fields.join(","));
+        line.push_str(&format!(" {}", self.timestamp));
+        line
+    }
+}
+
+/// Backslashes are always escaped, then the `special` characters.
+fn escape(text: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        // a line break would end the point
+        if c == '\n' {
+            escaped.push_str("\\n");
+            continue;
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_protocol_escape() {
+        let line = Point::new("download_log", 1700000000)
+            .tag("drive", "my drive,1")
+            .tag("api_key", "")
+            .field("file_name", FieldValue::String("/a \"b\".zip".to_owned()))
+            .field("status", FieldValue::Integer(200))
+            .field("offset", FieldValue::Integer(-1))
+            .to_line();
+        assert_eq!(line, r#"download_log,drive=my\ drive\,1 file_name="/a \"b\".zip",status=200i,offset=-1i 1700000000"#);
+    }
+}
@@ -1,13 +1,267 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::{Instant, timeout_at};
+use tracing::warn;
+use crate::config_loader::config_struct::{EventQueueSetting, SinkConfig};
+use crate::service::metrics::METRICS;
+
 pub mod influx_download_log;
+pub mod jsonl_download_log;
+pub mod line_protocol;
+pub mod sqlite_download_log;
+
+/// The first backoff of retrying a batch, it will be doubled after each retry.
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A day in milliseconds, the downloads are counted by UTC day.
+pub const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
 
+/// What a download request gets. Only `File` is counted as a download of the path in the statistics.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadKind {
+    /// a file, redirected to one of its mirrors
+    #[default]
+    File,
+    /// the mirrors of a file or of all the files in a directory
+    Metalink,
+    /// a ZIP archive of a directory
+    Archive,
+}
+
+impl DownloadKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadKind::File => "file",
+            DownloadKind::Metalink => "metalink",
+            DownloadKind::Archive => "archive",
+        }
+    }
+}
+
+/// # Download Event
+/// What is known about a download request once it is handled, including the refused ones.
+#[derive(Clone, Debug)]
 pub struct SideEffectProps {
     pub request_ip: String,
     pub user_agent: String,
     pub file_name: String,
+    pub kind: DownloadKind,
+    /// name of the API key, if the request is made with one
+    pub api_key: Option<String>,
+    /// the drive of the chosen mirror, if a single one is chosen
+    pub drive: Option<String>,
+    /// the host of the chosen mirror, the signed url itself is not recorded
+    pub mirror: Option<String>,
+    /// in bytes, the total of all the files for directories
+    pub file_size: Option<u64>,
+    /// the status code of the response
+    pub status: u16,
+    pub referer: Option<String>,
+    /// when the request is received
+    pub timestamp: SystemTime,
+}
+
+/// # Download Record
+/// A download event as it is stored by the local sinks, `timestamp` is in milliseconds since unix epoch.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DownloadRecord {
+    pub timestamp: u64,
+    pub path: String,
+    /// the records written before it was recorded are all files
+    #[serde(default)]
+    pub kind: DownloadKind,
+    pub ip: String,
+    pub user_agent: String,
+    pub api_key: Option<String>,
+    pub drive: Option<String>,
+    pub mirror: Option<String>,
+    pub file_size: Option<u64>,
+    pub status: u16,
+    pub referer: Option<String>,
 }
 
+impl From<&SideEffectProps> for DownloadRecord {
+    fn from(props: &SideEffectProps) -> Self {
+        DownloadRecord {
+            timestamp: props.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            path: props.file_name.clone(),
+            kind: props.kind,
+            ip: props.request_ip.clone(),
+            user_agent: props.user_agent.clone(),
+            api_key: props.api_key.clone(),
+            drive: props.drive.clone(),
+            mirror: props.mirror.clone(),
+            file_size: props.file_size,
+            status: props.status,
+            referer: props.referer.clone(),
+        }
+    }
+}
+
+/// A sink of download events, like a database.
 #[async_trait::async_trait]
-pub trait SideEffect<Config: Send + Sync>: Send + Sync {
-    fn new(config: Config) -> Self;
-    async fn do_effect(&self, props: SideEffectProps);
-}
\ No newline at end of file
+pub trait SideEffect: Send + Sync {
+    /// to label the metrics and the log
+    fn name(&self) -> &str;
+    /// Write a batch of events at once, a failed batch will be retried as a whole.
+    async fn do_effects(&self, batch: &[SideEffectProps]) -> Result<(), String>;
+}
+
+/// # Download Counts
+/// The successful downloads (2xx and 3xx) of files in the download log, counted from each of some starts.
+/// `per_path[path][i]` is the downloads of `path` at or after `starts[i]`,
+/// the days and the drives are counted from the earliest start.
+#[derive(Default, Debug)]
+pub struct DownloadCounts {
+    pub per_path: HashMap<String, Vec<u64>>,
+    /// by days since unix epoch
+    pub per_day: HashMap<u64, u64>,
+    pub per_drive: HashMap<String, u64>,
+}
+
+impl DownloadCounts {
+    /// Count a record, for the sinks which can not count by themselves.
+    pub fn add(&mut self, record: &DownloadRecord, starts: &[u64]) {
+        let earliest = starts.iter().copied().min().unwrap_or(0);
+        if record.kind != DownloadKind::File || !(200..400).contains(&record.status) || record.timestamp < earliest {
+            return;
+        }
+        let counts = self.per_path.entry(record.path.clone()).or_insert_with(|| vec![0; starts.len()]);
+        for (count, start) in counts.iter_mut().zip(starts) {
+            if record.timestamp >= *start {
+                *count += 1;
+            }
+        }
+        *self.per_day.entry(record.timestamp / DAY_MILLIS).or_default() += 1;
+        if let Some(drive) = &record.drive {
+            *self.per_drive.entry(drive.clone()).or_default() += 1;
+        }
+    }
+}
+
+/// A sink which can read the downloads back, for statistics.
+#[async_trait::async_trait]
+pub trait DownloadHistory: Send + Sync {
+    /// The successful downloads counted from each of `starts`, in milliseconds since unix epoch.
+    async fn count_since(&self, starts: &[u64]) -> Result<DownloadCounts, String>;
+}
+
+/// # Side Effects
+/// Queues the download events, so that neither slow nor failing sinks affect the response.
+/// Each sink has its own queue and background writer, which takes the events in batches and retries with backoff,
+/// so a failing sink does not hold back the others. While the queue of a sink is full, new events are dropped for it.
+pub struct SideEffects {
+    /// empty if no sink is configured
+    queues: Vec<SinkQueue>,
+    /// the first `sqlite` sink, or the first `jsonl` sink if there is no `sqlite` one
+    history: Option<Arc<dyn DownloadHistory>>,
+}
+
+impl SideEffects {
+    /// Fails if any sink is misconfigured.
+    pub fn new(configs: Vec<SinkConfig>, setting: EventQueueSetting) -> Result<Self, String> {
+        let mut sinks: Vec<Arc<dyn SideEffect>> = Vec::new();
+        let mut sqlite_history: Option<Arc<dyn DownloadHistory>> = None;
+        let mut jsonl_history: Option<Arc<dyn DownloadHistory>> = None;
+        for config in configs {
+            match config {
+                SinkConfig::Influx(config) => {
+                    sinks.push(Arc::new(influx_download_log::InfluxLog::new(config).map_err(|e| format!("influx: {}", e))?));
+                }
+                SinkConfig::Jsonl(config) => {
+                    let sink = Arc::new(jsonl_download_log::JsonlLog::new(config).map_err(|e| format!("jsonl: {}", e))?);
+                    jsonl_history.get_or_insert(sink.clone());
+                    sinks.push(sink);
+                }
+                SinkConfig::Sqlite(config) => {
+                    let sink = Arc::new(sqlite_download_log::SqliteLog::new(config).map_err(|e| format!("sqlite: {}", e))?);
+                    sqlite_history.get_or_insert(sink.clone());
+                    sinks.push(sink);
+                }
+            }
+        }
+        let history = sqlite_history.or(jsonl_history);
+        let queues = sinks.into_iter().map(|sink| {
+            let (sender, receiver) = mpsc::channel(setting.capacity.max(1));
+            let name = sink.name().to_owned();
+            tokio::spawn(write_batches(receiver, sender.downgrade(), sink, setting.clone()));
+            SinkQueue { name, sender }
+        }).collect();
+        Ok(SideEffects { queues, history })
+    }
+
+    /// The sink to read the downloads back from, `None` if there is neither `sqlite` nor `jsonl` sink.
+    pub fn history(&self) -> Option<Arc<dyn DownloadHistory>> {
+        self.history.clone()
+    }
+
+    pub fn emit(&self, props: SideEffectProps) {
+        for queue in &self.queues {
+            match queue.sender.try_send(props.clone()) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => METRICS.events_dropped.with_label_values(&[&queue.name, "overload"]).inc(),
+                Err(TrySendError::Closed(_)) => METRICS.events_dropped.with_label_values(&[&queue.name, "closed"]).inc(),
+            }
+            set_queue_depth(&queue.name, &queue.sender);
+        }
+    }
+}
+
+struct SinkQueue {
+    /// the name of the sink, to label the metrics
+    name: String,
+    sender: mpsc::Sender<SideEffectProps>,
+}
+
+fn set_queue_depth(sink: &str, sender: &mpsc::Sender<SideEffectProps>) {
+    METRICS.event_queue_depth.with_label_values(&[sink]).set((sender.max_capacity() - sender.capacity()) as i64);
+}
+
+/// Take up to `batch_size` events, waiting at most `flush_interval` after the first one, until all the senders are dropped.
+/// `sender` is only used to tell the queue depth, so it does not keep the queue open.
+async fn write_batches(mut receiver: mpsc::Receiver<SideEffectProps>, sender: mpsc::WeakSender<SideEffectProps>, sink: Arc<dyn SideEffect>, setting: EventQueueSetting) {
+    let batch_size = setting.batch_size.max(1);
+    while let Some(first) = receiver.recv().await {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + Duration::from_millis(setting.flush_interval);
+        while batch.len() < batch_size {
+            match timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(props)) => batch.push(props),
+                _ => break,
+            }
+        }
+        if let Some(sender) = sender.upgrade() {
+            set_queue_depth(sink.name(), &sender);
+        }
+        write_with_retry(sink.as_ref(), &batch, setting.max_retries).await;
+    }
+}
+
+/// Write the batch to the sink, the batch is dropped after `max_retries` retries.
+async fn write_with_retry(sink: &dyn SideEffect, batch: &[SideEffectProps], max_retries: u32) {
+    let mut attempt = 0;
+    loop {
+        match sink.do_effects(batch).await {
+            Ok(()) => {
+                METRICS.events_written.with_label_values(&[sink.name()]).inc_by(batch.len() as u64);
+                return;
+            }
+            Err(e) if attempt < max_retries => {
+                let backoff = (BASE_BACKOFF * 2u32.pow(attempt)).min(MAX_BACKOFF);
+                warn!("{}, retry in {:?}", e, backoff);
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                warn!("{}, {} events are dropped after {} retries", e, batch.len(), max_retries);
+                METRICS.events_dropped.with_label_values(&[sink.name(), "sink_failure"]).inc_by(batch.len() as u64);
+                return;
+            }
+        }
+    }
+}
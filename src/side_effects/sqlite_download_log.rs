@@ -0,0 +1,206 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use rusqlite::{Connection, params};
+use crate::config_loader::config_struct::SqliteSinkConfig;
+use crate::side_effects::{DAY_MILLIS, DownloadCounts, DownloadHistory, DownloadRecord, SideEffect, SideEffectProps};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS downloads (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    ip TEXT NOT NULL,
+    user_agent TEXT NOT NULL,
+    api_key TEXT,
+    drive TEXT,
+    mirror TEXT,
+    file_size INTEGER,
+    status INTEGER NOT NULL,
+    referer TEXT,
+    kind TEXT NOT NULL DEFAULT 'file'
+);
+CREATE INDEX IF NOT EXISTS downloads_timestamp ON downloads (timestamp);
+CREATE INDEX IF NOT EXISTS downloads_path ON downloads (path, timestamp);
+";
+
+/// Added after the first version of the table, the existing rows are all files.
+const ADD_KIND: &str = "ALTER TABLE downloads ADD COLUMN kind TEXT NOT NULL DEFAULT 'file'";
+
+/// # SQLite Download Log
+/// Inserts the downloads into the `downloads` table of an embedded database, a transaction per batch.
+/// `timestamp` is in milliseconds since unix epoch, and indexed with `path`.
+pub struct SqliteLog {
+    // the connection is used in blocking tasks, so it is shared by `Arc`
+    connection: Arc<Mutex<Connection>>,
+    retention: Option<Duration>,
+}
+
+impl SqliteLog {
+    /// Fails if the database can not be opened or migrated.
+    pub fn new(config: SqliteSinkConfig) -> Result<Self, String> {
+        let connection = Connection::open(&config.path)
+            .map_err(|e| format!("Can not open {}: {}", config.path, e))?;
+        Self::with_connection(connection, config.retention_days)
+    }
+
+    fn with_connection(connection: Connection, retention_days: Option<u64>) -> Result<Self, String> {
+        connection.execute_batch(SCHEMA).map_err(|e| format!("Failed to create tables: {}", e))?;
+        let has_kind = connection.prepare("SELECT kind FROM downloads LIMIT 0").is_ok();
+        if !has_kind {
+            connection.execute_batch(ADD_KIND).map_err(|e| format!("Failed to add column kind: {}", e))?;
+        }
+        Ok(SqliteLog {
+            connection: Arc::new(Mutex::new(connection)),
+            retention: retention_days.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+        })
+    }
+}
+
+fn insert(connection: &mut Connection, records: &[DownloadRecord], retention: Option<Duration>) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    {
+        let mut statement = transaction.prepare_cached(
+            "INSERT INTO downloads (timestamp, path, ip, user_agent, api_key, drive, mirror, file_size, status, referer, kind)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"
+        )?;
+        for record in records {
+            statement.execute(params![
+                record.timestamp as i64, record.path, record.ip, record.user_agent, record.api_key,
+                record.drive, record.mirror, record.file_size.map(|size| size as i64), record.status, record.referer,
+                record.kind.as_str(),
+            ])?;
+        }
+    }
+    if let Some(retention) = retention {
+        let expired = SystemTime::now().checked_sub(retention)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|time| time.as_millis() as i64)
+            .unwrap_or(0);
+        transaction.execute("DELETE FROM downloads WHERE timestamp < ?1", params![expired])?;
+    }
+    transaction.commit()
+}
+
+/// Count the downloads in the database, the range of `timestamp` is found by its index.
+fn count_since(connection: &Connection, starts: &[u64]) -> rusqlite::Result<DownloadCounts> {
+    let earliest = starts.iter().copied().min().unwrap_or(0) as i64;
+    let succeeded = "timestamp >= ?1 AND status >= 200 AND status < 400 AND kind = 'file'";
+    let mut counts = DownloadCounts::default();
+
+    // `?1` is the earliest start, and the starts are `?2` and on
+    let windows: Vec<String> = (0..starts.len()).map(|index| format!("SUM(timestamp >= ?{})", index + 2)).collect();
+    let sql = format!("SELECT path, {} FROM downloads WHERE {} GROUP BY path", windows.join(", "), succeeded);
+    let mut statement = connection.prepare(&sql)?;
+    let mut parameters = vec![earliest];
+    parameters.extend(starts.iter().map(|start| *start as i64));
+    let mut rows = statement.query(rusqlite::params_from_iter(parameters))?;
+    while let Some(row) = rows.next()? {
+        let path: String = row.get(0)?;
+        let per_window = (0..starts.len()).map(|index| row.get::<_, i64>(index + 1).map(|count| count as u64))
+            .collect::<rusqlite::Result<_>>()?;
+        counts.per_path.insert(path, per_window);
+    }
+
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT timestamp / ?2, COUNT(*) FROM downloads WHERE {} GROUP BY 1", succeeded
+    ))?;
+    let rows = statement.query_map(params![earliest, DAY_MILLIS as i64], |row| {
+        Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64))
+    })?;
+    counts.per_day = rows.collect::<rusqlite::Result<_>>()?;
+
+    let mut statement = connection.prepare_cached(&format!(
+        "SELECT drive, COUNT(*) FROM downloads WHERE {} AND drive IS NOT NULL GROUP BY drive", succeeded
+    ))?;
+    let rows = statement.query_map(params![earliest], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?;
+    counts.per_drive = rows.collect::<rusqlite::Result<_>>()?;
+    Ok(counts)
+}
+
+#[async_trait::async_trait]
+impl DownloadHistory for SqliteLog {
+    async fn count_since(&self, starts: &[u64]) -> Result<DownloadCounts, String> {
+        let connection = self.connection.clone();
+        let starts = starts.to_vec();
+        tokio::task::spawn_blocking(move || count_since(&connection.lock().unwrap(), &starts))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to read from sqlite: {}", e))
+    }
+}
+
+#[async_trait::async_trait]
+impl SideEffect for SqliteLog {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    async fn do_effects(&self, batch: &[SideEffectProps]) -> Result<(), String> {
+        let records: Vec<DownloadRecord> = batch.iter().map(DownloadRecord::from).collect();
+        let connection = self.connection.clone();
+        let retention = self.retention;
+        tokio::task::spawn_blocking(move || insert(&mut connection.lock().unwrap(), &records, retention))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to write to sqlite: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::side_effects::DownloadKind;
+    use super::*;
+
+    fn record(timestamp: u64, path: &str) -> DownloadRecord {
+        DownloadRecord {
+            timestamp,
+            path: path.to_owned(),
+            kind: DownloadKind::File,
+            ip: "127.0.0.1".to_owned(),
+            user_agent: String::new(),
+            api_key: None,
+            drive: Some("d1".to_owned()),
+            mirror: None,
+            file_size: Some(1),
+            status: 307,
+            referer: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_insert_and_retention() {
+        let log = SqliteLog::with_connection(Connection::open_in_memory().unwrap(), None).unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let old = now - 3 * DAY_MILLIS;
+        let mut failed = record(now, "/new");
+        failed.status = 403;
+        let mut archive = record(now, "/new");
+        archive.kind = DownloadKind::Archive;
+        insert(&mut log.connection.lock().unwrap(), &[record(old, "/old"), record(now, "/new"), failed, archive], None).unwrap();
+        let counts = log.count_since(&[now, 0]).await.unwrap();
+        assert_eq!(counts.per_path["/old"], vec![0, 1]);
+        assert_eq!(counts.per_path["/new"], vec![1, 1]);
+        assert_eq!(counts.per_day.values().sum::<u64>(), 2);
+        assert_eq!(counts.per_day[&(now / DAY_MILLIS)], 1);
+        assert_eq!(counts.per_drive["d1"], 2);
+
+        // the downloads older than the retention are deleted by the next insert
+        let retention = Some(Duration::from_secs(24 * 60 * 60));
+        insert(&mut log.connection.lock().unwrap(), &[record(now, "/newer")], retention).unwrap();
+        let counts = log.count_since(&[0]).await.unwrap();
+        let mut kept: Vec<&String> = counts.per_path.keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["/new", "/newer"]);
+    }
+
+    #[test]
+    fn test_sqlite_adds_kind_to_old_tables() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(&SCHEMA.replace(",\n    kind TEXT NOT NULL DEFAULT 'file'", "")).unwrap();
+        assert!(connection.prepare("SELECT kind FROM downloads").is_err());
+        connection.execute("INSERT INTO downloads (timestamp, path, ip, user_agent, status) VALUES (1, '/a', '', '', 200)", []).unwrap();
+        let log = SqliteLog::with_connection(connection, None).unwrap();
+        let counts = count_since(&log.connection.lock().unwrap(), &[0]).unwrap();
+        assert_eq!(counts.per_path["/a"], vec![1]);
+    }
+}
@@ -1,8 +1,13 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use crate::config_loader::config_struct::ConflictPolicy;
+use crate::service::drive_health::DriveHealth;
 use crate::vfs::{VfsBasicMeta, VfsDir, VfsEntry, VfsFile};
-use rand::Rng;
+use crate::vfs::select::MirrorSelector;
+use crate::vfs::visibility::{PathFilter, Visibility};
 use std::marker::Send;
+use tracing::warn;
 
 
 #[derive(Clone)]
@@ -14,6 +19,7 @@ pub struct CombinableVfsDir {
     _sub_dirs: Vec<CombinableVfsDir>,
     _files: Vec<CombinableVfsFile>,
     _size: u64,
+    _unlisted: bool,
 }
 
 impl CombinableVfsDir {
@@ -23,8 +29,13 @@ impl CombinableVfsDir {
             _sub_dirs: sub_dirs,
             _files: files,
             _size: size,
+            _unlisted: false,
         }
     }
+    /// Whether the directory is left out of listings, though it can still be reached by its exact path.
+    pub fn is_unlisted(&self) -> bool {
+        self._unlisted
+    }
 }
 
 // impl VfsDir for CombinableVfsDir
@@ -36,7 +47,10 @@ impl VfsBasicMeta for CombinableVfsDir {
         self._size
     }
     fn last_modified(&self) -> std::time::SystemTime {
-        self._files.iter().map(|file| file.last_modified()).max().unwrap()
+        // a directory without any file is as old as its newest sub directory
+        self._files.iter().map(|file| file.last_modified())
+            .chain(self._sub_dirs.iter().map(|dir| dir.last_modified()))
+            .max().unwrap_or(std::time::UNIX_EPOCH)
     }
 }
 
@@ -50,31 +64,195 @@ impl VfsDir<CombinableVfsFile> for CombinableVfsDir {
     }
 }
 
+/// # Link Resolver
+/// Some cloud drives (like OneDrive) only give out download urls which expire after a while.
+/// The resolver requests a fresh download url of one file just in time.
+#[async_trait::async_trait]
+pub trait LinkResolver: Send + Sync {
+    async fn resolve(&self) -> Result<String, String>;
+}
+
+#[derive(Debug)]
+/// # Drive Tag
+/// Tells which drive a download link comes from.
+pub struct DriveTag {
+    pub name: String,
+    /// Larger is preferred when files on different drives conflict, and gets more downloads when mirrors are selected by weight.
+    pub priority: i64,
+}
+
+struct FetchedUrl {
+    url: String,
+    fetched_at: SystemTime,
+}
+
+#[derive(Clone)]
+/// # Download Link
+/// A download url of a file on one drive.
+/// If the link has a resolver and the url is older than `max_age`, a fresh url will be resolved when downloading.
+pub struct DownloadLink {
+    // shared by all the clones, so that a resolved url can be reused until it is too old again
+    current: Arc<Mutex<FetchedUrl>>,
+    refresh: Option<(Arc<dyn LinkResolver>, Duration)>,
+    drive: Arc<DriveTag>,
+}
+
+impl DownloadLink {
+    /// A download url fetched at `fetched_at`, which should be resolved again by `resolver` when it is older than `max_age`.
+    pub fn expiring(url: String, fetched_at: SystemTime, resolver: Arc<dyn LinkResolver>, max_age: Duration, drive: Arc<DriveTag>) -> Self {
+        DownloadLink {
+            current: Arc::new(Mutex::new(FetchedUrl {
+                url,
+                fetched_at,
+            })),
+            refresh: Some((resolver, max_age)),
+            drive,
+        }
+    }
+
+    pub fn drive(&self) -> &DriveTag {
+        &self.drive
+    }
+
+    /// Get the download url, resolve a fresh one if the current one is too old.
+    /// When resolving failed, the old url will still be returned, and the drive will be marked unhealthy.
+    pub async fn url(&self, health: &DriveHealth) -> String {
+        let (stale_url, resolver) = {
+            let current = self.current.lock().unwrap();
+            match &self.refresh {
+                Some((resolver, max_age)) if is_older_than(current.fetched_at, *max_age) => {
+                    (current.url.clone(), resolver.clone())
+                }
+                _ => return current.url.clone(),
+            }
+        };
+        match resolver.resolve().await {
+            Ok(url) => {
+                health.mark_healthy(&self.drive.name);
+                *self.current.lock().unwrap() = FetchedUrl {
+                    url: url.clone(),
+                    fetched_at: SystemTime::now(),
+                };
+                url
+            }
+            Err(e) => {
+                warn!("Failed to resolve a fresh download url, the old one is used: {}", e);
+                health.mark_failed(&self.drive.name, e);
+                stale_url
+            }
+        }
+    }
+}
+
+fn is_older_than(time: SystemTime, max_age: Duration) -> bool {
+    match time.elapsed() {
+        Ok(age) => age > max_age,
+        Err(_) => false,
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// # Content Hash
+/// The hashes of a file given by the cloud drive. Different drives give different kinds of hashes, so all of them are optional.
+pub struct ContentHash {
+    /// SHA-1 in lowercase hex (OneDrive)
+    pub sha1: Option<String>,
+    /// QuickXorHash in base64 (OneDrive)
+    pub quick_xor: Option<String>,
+    /// ETag of the object (S3)
+    pub etag: Option<String>,
+}
+
+impl ContentHash {
+    /// Whether two files have the same content as far as their hashes can tell.
+    /// They must have at least one kind of hash in common, and every kind of hash known by both sides must be equal.
+    /// Without a kind in common they can not be told apart, so they do not agree.
+    pub fn agrees_with(&self, other: &ContentHash) -> bool {
+        let pairs = [(&self.sha1, &other.sha1), (&self.quick_xor, &other.quick_xor), (&self.etag, &other.etag)];
+        let mut common = pairs.into_iter().filter_map(|pair| match pair {
+            (Some(a), Some(b)) => Some(a == b),
+            _ => None,
+        }).peekable();
+        common.peek().is_some() && common.all(|equal| equal)
+    }
+
+    /// Whether the drive gives no hash at all.
+    fn is_empty(&self) -> bool {
+        self.sha1.is_none() && self.quick_xor.is_none() && self.etag.is_none()
+    }
+
+    /// Fill the kinds of hash missing in `self` with `other`.
+    fn merge(&mut self, other: &ContentHash) {
+        self.sha1 = self.sha1.take().or_else(|| other.sha1.clone());
+        self.quick_xor = self.quick_xor.take().or_else(|| other.quick_xor.clone());
+        self.etag = self.etag.take().or_else(|| other.etag.clone());
+    }
+}
+
 #[derive(Clone)]
 /// # VFS File
 /// The implement of `VfsFile` trait. As a virtual file system file, it contains the download link and other meta information.
 pub struct CombinableVfsFile {
-    _links: Vec<String>,
     _name: String,
     _size: u64,
     _last_modified: std::time::SystemTime,
-    _on_download: Arc<dyn Send + Sync + Fn() -> String>,
+    _hash: ContentHash,
+    _mirrors: Arc<MirrorSelector>,
+    _unlisted: bool,
 }
 
 impl CombinableVfsFile {
-    fn possible_on_download(&self) -> Vec<String> {
-        self._links.clone()
+    pub fn possible_on_download(&self) -> Vec<DownloadLink> {
+        self._mirrors.links().to_vec()
     }
-    pub fn new(links: Vec<String>, name: String, size: u64, last_modified: std::time::SystemTime) -> Self {
-        let on_download = get_random_selector(links.len(), links.clone());
+    pub fn new(links: Vec<DownloadLink>, name: String, size: u64, last_modified: std::time::SystemTime, hash: ContentHash) -> Self {
         CombinableVfsFile {
-            _links: links,
             _name: name,
             _size: size,
             _last_modified: last_modified,
-            _on_download: Arc::new(on_download),
+            _hash: hash,
+            _mirrors: Arc::new(MirrorSelector::new(links)),
+            _unlisted: false,
         }
     }
+    pub fn mirrors(&self) -> &MirrorSelector {
+        &self._mirrors
+    }
+    pub fn hash(&self) -> &ContentHash {
+        &self._hash
+    }
+    /// Whether the file is left out of listings, though it can still be downloaded by its exact path.
+    pub fn is_unlisted(&self) -> bool {
+        self._unlisted
+    }
+    /// Whether the two files can be mirrors of each other.
+    /// Files without any hash on both sides can only be told apart by their sizes, so the same size is enough for them.
+    fn is_mirror_of(&self, other: &CombinableVfsFile) -> bool {
+        let no_hash = self._hash.is_empty() && other._hash.is_empty();
+        self._size == other._size && (no_hash || self._hash.agrees_with(&other._hash))
+    }
+    /// The highest priority of the drives which the file is on.
+    fn priority(&self) -> i64 {
+        self._mirrors.links().iter().map(|link| link.drive().priority).max().unwrap_or(0)
+    }
+    /// Rename the file to `name (drive).ext`, where `drive` is the drive which the file is on.
+    /// If the name is in `taken`, it is numbered like `name (drive 2).ext` instead, then the new name is taken.
+    fn rename_with_drive_suffix(mut self, taken: &mut HashSet<String>) -> Self {
+        let drive = self._mirrors.links().first().map(|link| link.drive().name.clone()).unwrap_or_default();
+        let (stem, ext) = match self._name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem.to_owned(), format!(".{}", ext)),
+            _ => (self._name.clone(), String::new()),
+        };
+        let mut name = format!("{} ({}){}", stem, drive, ext);
+        let mut number = 2;
+        while taken.contains(&name) {
+            name = format!("{} ({} {}){}", stem, drive, number, ext);
+            number += 1;
+        }
+        taken.insert(name.clone());
+        self._name = name;
+        self
+    }
 }
 
 // impl VfsFile for CombinableVfsFile
@@ -90,36 +268,128 @@ impl VfsBasicMeta for CombinableVfsFile {
     }
 }
 
-impl VfsFile for CombinableVfsFile {
-    fn on_download(&self) -> String {
-        (self._on_download)()
-    }
-}
+impl VfsFile for CombinableVfsFile {}
 
-/// When 2 files with same name (and same size, etc) are combined, the download link will be randomly selected from the 2 files.
+/// When 2 files with same name (and same size and hash) are combined, the download link will be selected from the 2 files.
 fn combine_vfs_files(files: Vec<CombinableVfsFile>) -> CombinableVfsFile {
-    let maybe_files: Vec<String> = files.iter()
-        .map(|file| file.possible_on_download())
-        .flatten().collect();
-    let on_download = get_random_selector(maybe_files.len(), maybe_files.clone());
-    return CombinableVfsFile {
-        _links: maybe_files,
-        _name: files[0].name().to_owned(),
-        _size: files[0].size(),
-        _last_modified: files.iter().map(|file| file.last_modified()).max().unwrap(),
-        _on_download: Arc::new(on_download),
-    };
+    let maybe_files: Vec<DownloadLink> = files.iter()
+        .flat_map(|file| file.possible_on_download())
+        .collect();
+    let mut hash = ContentHash::default();
+    files.iter().for_each(|file| hash.merge(file.hash()));
+    let mut combined = CombinableVfsFile::new(
+        maybe_files,
+        files[0].name().to_owned(),
+        files[0].size(),
+        files.iter().map(|file| file.last_modified()).max().unwrap(),
+        hash,
+    );
+    // unlisted on any drive is unlisted
+    combined._unlisted = files.iter().any(|file| file._unlisted);
+    combined
 }
 
-/// High level function to get a random selector function.
-fn get_random_selector<T: Clone>(n: usize, possibles: Vec<T>) -> impl Fn() -> T {
-    move || {
-        let index = rand::thread_rng().gen_range(0..n);
-        possibles[index].clone()
+/// Group the files with same name into mirrors, files in the same group have the same size and hash.
+fn group_mirrors(files: Vec<CombinableVfsFile>) -> Vec<Vec<CombinableVfsFile>> {
+    let mut groups: Vec<Vec<CombinableVfsFile>> = Vec::new();
+    for file in files {
+        match groups.iter_mut().find(|group| group.iter().all(|mirror| mirror.is_mirror_of(&file))) {
+            Some(group) => group.push(file),
+            None => groups.push(vec![file]),
+        }
+    }
+    groups
+}
+
+/// Combine the files with same name.
+/// Files with same size and hash are mirrors, otherwise they conflict and are resolved by `policy`.
+/// `taken` is the names in the directory, which the renamed files must not collide with.
+fn combine_same_name_files(files: Vec<CombinableVfsFile>, policy: ConflictPolicy, taken: &mut HashSet<String>) -> Result<Vec<CombinableVfsFile>, String> {
+    let mut files: Vec<CombinableVfsFile> = group_mirrors(files).into_iter()
+        .map(combine_vfs_files)
+        .collect();
+    if files.len() == 1 {
+        return Ok(files);
+    }
+    match policy {
+        ConflictPolicy::Rename => {
+            // the one with the highest priority keeps its name
+            files.sort_by_key(|file| std::cmp::Reverse(file.priority()));
+            let mut files = files.into_iter();
+            let kept = files.next().into_iter();
+            Ok(kept.chain(files.map(|file| file.rename_with_drive_suffix(taken))).collect())
+        }
+        ConflictPolicy::Newest => {
+            Ok(files.into_iter().max_by_key(|file| file.last_modified()).into_iter().collect())
+        }
+        ConflictPolicy::Priority => {
+            Ok(files.into_iter().max_by_key(|file| file.priority()).into_iter().collect())
+        }
+        ConflictPolicy::Error => {
+            Err(format!("{} has different contents on different drives", files[0].name()))
+        }
     }
 }
 
 impl CombinableVfsDir {
+    /// All the listed files in the directory recursively, with their paths prefixed by `prefix`.
+    /// Files and sub directories are skipped if `include(path)` is false.
+    pub fn walk_files(&self, prefix: &str, include: &dyn Fn(&str) -> bool) -> Vec<(String, CombinableVfsFile)> {
+        let mut files = Vec::new();
+        for file in self._files.iter().filter(|file| !file._unlisted) {
+            let file_path = format!("{}/{}", prefix, file.name());
+            if include(&file_path) {
+                files.push((file_path, file.clone()));
+            }
+        }
+        for dir in self._sub_dirs.iter().filter(|dir| !dir._unlisted) {
+            let dir_path = format!("{}/{}", prefix, dir.name());
+            if include(&dir_path) {
+                files.extend(dir.walk_files(&dir_path, include));
+            }
+        }
+        files
+    }
+
+    /// The number of files and directories in the directory recursively.
+    pub fn item_count(&self) -> u64 {
+        self._files.len() as u64 + self._sub_dirs.iter().map(|dir| 1 + dir.item_count()).sum::<u64>()
+    }
+
+    /// Remove the hidden entries and mark the unlisted ones by `filter`, `path` is the path of the directory itself.
+    /// The size is calculated again without the hidden entries.
+    pub fn apply_filter(self, filter: &PathFilter, path: &str) -> Self {
+        if filter.is_empty() {
+            return self;
+        }
+        let sub_dirs: Vec<CombinableVfsDir> = self._sub_dirs.into_iter().filter_map(|mut dir| {
+            let dir_path = format!("{}/{}", path, dir.name());
+            match filter.visibility(&dir_path, true) {
+                Visibility::Hidden => return None,
+                Visibility::Unlisted => dir._unlisted = true,
+                Visibility::Listed => {}
+            }
+            Some(dir.apply_filter(filter, &dir_path))
+        }).collect();
+        let files: Vec<CombinableVfsFile> = self._files.into_iter().filter_map(|mut file| {
+            match filter.visibility(&format!("{}/{}", path, file.name()), false) {
+                Visibility::Hidden => return None,
+                Visibility::Unlisted => file._unlisted = true,
+                Visibility::Listed => {}
+            }
+            Some(file)
+        }).collect();
+        let size = files.iter().map(|file| file.size()).sum::<u64>()
+            + sub_dirs.iter().map(|dir| dir.size()).sum::<u64>();
+        CombinableVfsDir {
+            _name: self._name,
+            _sub_dirs: sub_dirs,
+            _files: files,
+            _size: size,
+            _unlisted: self._unlisted,
+        }
+    }
+
     /// Destruct the `CombinableVfsDir` into
     /// - sub directories: `Vec<CombinableVfsDir>`
     /// - files: `Vec<CombinableVfsFile>`
@@ -138,11 +408,20 @@ impl CombinableVfsDir {
 /// When combined:
 /// - sub directories with same name will be combined recursively.
 /// - sub directories in only one of the `CombinableVfsDir` will be kept as is.
-/// - files with same name will be combined into a new file with `combine_vfs_files`.
+/// - files with same name, size and hash (or no hash on either side) will be combined into a new file with `combine_vfs_files`.
+/// - files with same name but different contents conflict, and are resolved by `policy`.
 /// - files in only one of the `CombinableVfsDir` will be kept as is.
 /// - the size of files which are in more than one of the `CombinableVfsDir` will be added up once, not multiple times.
 /// - the new size is the sum of all files' size.
-pub fn combine_vfs_dirs(dirs: Vec<CombinableVfsDir>) -> CombinableVfsDir {
+/// - entries unlisted on any of the drives are unlisted.
+///
+/// Only `ConflictPolicy::Error` and an empty `dirs` can fail, the error tells the path of the conflicting file.
+pub fn combine_vfs_dirs(dirs: Vec<CombinableVfsDir>, policy: ConflictPolicy) -> Result<CombinableVfsDir, String> {
+    if dirs.is_empty() {
+        return Err("No directory to combine".to_owned());
+    }
+    // unlisted on any drive is unlisted
+    let unlisted = dirs.iter().any(|dir| dir._unlisted);
     // destruct all dirs
     let dirs: Vec<(Vec<CombinableVfsDir>, Vec<CombinableVfsFile>, u64, String)> = dirs.into_iter()
         .map(|dir| dir.destruct()).collect::<Vec<_>>();
@@ -169,12 +448,18 @@ pub fn combine_vfs_dirs(dirs: Vec<CombinableVfsDir>) -> CombinableVfsDir {
     let sub_dirs = separate_by_name(sub_dirs);
     let files = separate_by_name(files);
     // then combine them
-    let sub_dirs: Vec<CombinableVfsDir> = sub_dirs.into_iter().map(|(_, dirs)| {
-        combine_vfs_dirs(dirs)
-    }).collect();
+    let sub_dirs: Vec<CombinableVfsDir> = sub_dirs.into_values().map(|dirs| {
+        combine_vfs_dirs(dirs, policy)
+    }).collect::<Result<_, _>>().map_err(|e| format!("{}/{}", name, e))?;
+    // the renamed files must not collide with the other entries, nor with each other.
+    // they are renamed in the order of their names, so that the numbers stay the same across refreshes
+    let mut taken: HashSet<String> = files.keys().map(String::as_str).chain(sub_dirs.iter().map(|dir| dir.name())).map(str::to_owned).collect();
+    let mut files: Vec<(String, Vec<CombinableVfsFile>)> = files.into_iter().collect();
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
     let files: Vec<CombinableVfsFile> = files.into_iter().map(|(_, files)| {
-        combine_vfs_files(files)
-    }).collect();
+        combine_same_name_files(files, policy, &mut taken)
+    }).collect::<Result<Vec<_>, _>>().map_err(|e| format!("{}/{}", name, e))?
+        .into_iter().flatten().collect();
 
     // calculate the size
     let size: u64 =
@@ -185,23 +470,108 @@ pub fn combine_vfs_dirs(dirs: Vec<CombinableVfsDir>) -> CombinableVfsDir {
                 .map(|dir| dir.size()).sum::<u64>();
 
     // return the new dir
-    CombinableVfsDir {
+    Ok(CombinableVfsDir {
         _name: name,
         _sub_dirs: sub_dirs,
         _files: files,
         _size: size,
-    }
+        _unlisted: unlisted,
+    })
 }
 
 fn separate_by_name<T: VfsBasicMeta>(flat: Vec<T>) -> HashMap<String, Vec<T>> {
     let mut map: HashMap<String, Vec<T>> = HashMap::new();
     for entry in flat {
-        let name = entry.name().to_owned();
-        if map.contains_key(&name) {
-            map.get_mut(&name).unwrap().push(entry);
-        } else {
-            map.insert(name, vec![entry]);
-        }
+        map.entry(entry.name().to_owned()).or_default().push(entry);
     }
     map
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NeverResolve;
+
+    #[async_trait::async_trait]
+    impl LinkResolver for NeverResolve {
+        async fn resolve(&self) -> Result<String, String> {
+            Err("never".to_owned())
+        }
+    }
+
+    fn file_on(drive: &str, priority: i64, name: &str, size: u64, sha1: &str) -> CombinableVfsFile {
+        let tag = Arc::new(DriveTag { name: drive.to_owned(), priority });
+        let link = DownloadLink::expiring(
+            format!("https://{}/{}", drive, name),
+            SystemTime::now(),
+            Arc::new(NeverResolve),
+            Duration::from_secs(3600),
+            tag,
+        );
+        let hash = ContentHash { sha1: Some(sha1.to_owned()), ..Default::default() };
+        CombinableVfsFile::new(vec![link], name.to_owned(), size, SystemTime::now(), hash)
+    }
+
+    fn root_with(file: CombinableVfsFile) -> CombinableVfsDir {
+        CombinableVfsDir::new("root".to_owned(), vec![], vec![file], 0)
+    }
+
+    fn file_names(dir: &CombinableVfsDir) -> Vec<String> {
+        let mut names: Vec<String> = dir._files.iter().map(|file| file.name().to_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_combine_same_content_as_mirrors() {
+        let a = root_with(file_on("a", 0, "setup.exe", 10, "abc"));
+        let b = root_with(file_on("b", 0, "setup.exe", 10, "abc"));
+        let combined = combine_vfs_dirs(vec![a, b], ConflictPolicy::Error).unwrap();
+        assert_eq!(file_names(&combined), vec!["setup.exe"]);
+        assert_eq!(combined._files[0].possible_on_download().len(), 2);
+    }
+
+    #[test]
+    fn test_combine_conflicting_content() {
+        let dirs = || vec![
+            root_with(file_on("a", 0, "setup.exe", 10, "abc")),
+            root_with(file_on("b", 1, "setup.exe", 10, "def")),
+        ];
+
+        let renamed = combine_vfs_dirs(dirs(), ConflictPolicy::Rename).unwrap();
+        assert_eq!(file_names(&renamed), vec!["setup (a).exe", "setup.exe"]);
+
+        let preferred = combine_vfs_dirs(dirs(), ConflictPolicy::Priority).unwrap();
+        assert_eq!(preferred._files.len(), 1);
+        assert_eq!(preferred._files[0].possible_on_download()[0].drive().name, "b");
+
+        assert!(combine_vfs_dirs(dirs(), ConflictPolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_combine_without_hash_and_rename_collisions() {
+        let no_hash = |drive: &str, size: u64| {
+            let mut file = file_on(drive, 0, "a.txt", size, "");
+            file._hash = ContentHash::default();
+            file
+        };
+        let combined = combine_vfs_dirs(vec![root_with(no_hash("a", 1)), root_with(no_hash("b", 1))], ConflictPolicy::Rename).unwrap();
+        assert_eq!(file_names(&combined), vec!["a.txt"]);
+
+        let existing = CombinableVfsDir::new("root".to_owned(), vec![], vec![file_on("c", 0, "a (b).txt", 1, "x")], 0);
+        let dirs = vec![root_with(no_hash("a", 1)), root_with(no_hash("b", 2)), existing];
+        let renamed = combine_vfs_dirs(dirs, ConflictPolicy::Rename).unwrap();
+        assert_eq!(file_names(&renamed), vec!["a (b 2).txt", "a (b).txt", "a.txt"]);
+
+        assert!(combine_vfs_dirs(vec![], ConflictPolicy::Rename).is_err());
+    }
+
+    #[test]
+    fn test_hashes_without_common_kind_disagree() {
+        let sha1 = ContentHash { sha1: Some("abc".to_owned()), ..Default::default() };
+        let etag = ContentHash { etag: Some("abc".to_owned()), ..Default::default() };
+        assert!(sha1.agrees_with(&sha1));
+        assert!(!sha1.agrees_with(&etag));
+        assert!(!ContentHash::default().agrees_with(&ContentHash::default()));
+    }
+}
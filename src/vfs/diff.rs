@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::time::UNIX_EPOCH;
+use serde::Serialize;
+use crate::vfs::combine::CombinableVfsDir;
+use crate::vfs::VfsBasicMeta;
+
+/// The size and the last modified time in milliseconds of each listed file, by path like `/a/b.zip`.
+pub type FileIndex = BTreeMap<String, (u64, u128)>;
+
+pub fn index_files(dir: &CombinableVfsDir) -> FileIndex {
+    dir.walk_files("", &|_| true).into_iter()
+        .map(|(path, file)| {
+            let last_modified = file.last_modified().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+            (path, (file.size(), last_modified))
+        })
+        .collect()
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ChangedFile {
+    pub path: String,
+    pub size: u64,
+    /// in milliseconds since unix epoch
+    pub last_modified: u128,
+}
+
+/// # Tree Diff
+/// The files added, removed and modified between two snapshots of the tree, each sorted by path.
+/// A file is modified when its size or last modified time changes, the removed ones are as they were.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct TreeDiff {
+    pub added: Vec<ChangedFile>,
+    pub removed: Vec<ChangedFile>,
+    pub modified: Vec<ChangedFile>,
+}
+
+impl TreeDiff {
+    pub fn new(old: &FileIndex, new: &FileIndex) -> Self {
+        let changed = |path: &String, &(size, last_modified): &(u64, u128)| ChangedFile {
+            path: path.clone(),
+            size,
+            last_modified,
+        };
+        let mut diff = TreeDiff::default();
+        for (path, meta) in new {
+            match old.get(path) {
+                None => diff.added.push(changed(path, meta)),
+                Some(old_meta) if old_meta != meta => diff.modified.push(changed(path, meta)),
+                Some(_) => {}
+            }
+        }
+        diff.removed = old.iter()
+            .filter(|(path, _)| !new.contains_key(*path))
+            .map(|(path, meta)| changed(path, meta))
+            .collect();
+        diff
+    }
+
+    /// Only the files where `include(path)` is true.
+    pub fn filter(&self, include: &dyn Fn(&str) -> bool) -> Self {
+        let filter = |files: &[ChangedFile]| files.iter().filter(|file| include(&file.path)).cloned().collect();
+        TreeDiff {
+            added: filter(&self.added),
+            removed: filter(&self.removed),
+            modified: filter(&self.modified),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_diff() {
+        let old: FileIndex = [
+            ("/a".to_owned(), (1, 10)),
+            ("/b".to_owned(), (2, 10)),
+            ("/c".to_owned(), (3, 10)),
+        ].into_iter().collect();
+        let new: FileIndex = [
+            ("/a".to_owned(), (1, 10)),
+            ("/c".to_owned(), (3, 20)),
+            ("/d/e".to_owned(), (4, 10)),
+        ].into_iter().collect();
+        let diff = TreeDiff::new(&old, &new);
+        assert_eq!(diff.added.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(), ["/d/e"]);
+        assert_eq!(diff.removed[0].path, "/b");
+        assert_eq!(diff.modified[0].last_modified, 20);
+        assert!(TreeDiff::new(&new, &new).is_empty());
+        assert!(diff.filter(&|path| path.starts_with("/d/")).removed.is_empty());
+    }
+}
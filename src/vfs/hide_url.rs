@@ -15,6 +15,8 @@ pub struct UrlHiddenDir {
     name: String,
     size: u64,
     last_modified: std::time::SystemTime,
+    /// Protected by password, the children are only shown after it is unlocked.
+    locked: bool,
     children: Vec<UrlHiddenEntry>,
 }
 
@@ -29,44 +31,147 @@ impl Serialize for UrlHiddenFile {
         where
             S: Serializer,
     {
-        let mut state = serializer.serialize_struct("UrlHiddenFile", 4)?;
+        FileView { file: self, downloads: None }.serialize(serializer)
+    }
+}
+
+impl UrlHiddenDir {
+    /// The tree as seen by a user, only the entries where `can_list(path)` is true are shown,
+    /// and children of the locked directories are only shown if `unlocked(path)` is true.
+    /// Files carry `downloads` if `downloads(path)` gives it.
+    /// `path` of the root is empty, and the others are like `/dir/sub_dir`.
+    pub fn view<'a>(
+        &'a self,
+        can_list: &'a dyn Fn(&str) -> bool,
+        unlocked: &'a dyn Fn(&str) -> bool,
+        downloads: &'a dyn Fn(&str) -> Option<u64>,
+    ) -> impl Serialize + 'a {
+        DirView {
+            dir: self,
+            path: String::new(),
+            can_list,
+            unlocked,
+            downloads,
+        }
+    }
+}
+
+/// Serializing the tree directly shows none of the locked directories.
+impl Serialize for UrlHiddenDir {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        self.view(&|_| true, &|_| false, &|_| None).serialize(serializer)
+    }
+}
+
+struct FileView<'a> {
+    file: &'a UrlHiddenFile,
+    downloads: Option<u64>,
+}
+
+impl Serialize for FileView<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        let file = self.file;
+        let mut state = serializer.serialize_struct("UrlHiddenFile", 5)?;
         state.serialize_field("_type", "file")?;
-        state.serialize_field("name", &self.name)?;
-        state.serialize_field("size", &self.size)?;
-        let last_modified = self.last_modified.duration_since(UNIX_EPOCH)
+        state.serialize_field("name", &file.name)?;
+        state.serialize_field("size", &file.size)?;
+        let last_modified = file.last_modified.duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
             .as_millis();
         state.serialize_field("last_modified", &last_modified)?;
+        if let Some(downloads) = self.downloads {
+            state.serialize_field("downloads", &downloads)?;
+        }
         state.end()
     }
 }
 
-impl Serialize for UrlHiddenDir {
+struct DirView<'a> {
+    dir: &'a UrlHiddenDir,
+    path: String,
+    can_list: &'a dyn Fn(&str) -> bool,
+    unlocked: &'a dyn Fn(&str) -> bool,
+    downloads: &'a dyn Fn(&str) -> Option<u64>,
+}
+
+struct ChildrenView<'a>(&'a DirView<'a>);
+
+impl Serialize for DirView<'_> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: Serializer,
     {
-        let mut state = serializer.serialize_struct("UrlHiddenDir", 4)?;
+        let dir = self.dir;
+        let mut state = serializer.serialize_struct("UrlHiddenDir", 6)?;
         state.serialize_field("_type", "dir")?;
-        state.serialize_field("name", &self.name)?;
-        state.serialize_field("size", &self.size)?;
-        let last_modified = self.last_modified.duration_since(UNIX_EPOCH)
+        state.serialize_field("name", &dir.name)?;
+        state.serialize_field("size", &dir.size)?;
+        let last_modified = dir.last_modified.duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
             .as_millis();
         state.serialize_field("last_modified", &last_modified)?;
-        state.serialize_field("children", &self.children)?;
+        let locked = dir.locked && !(self.unlocked)(&self.path);
+        state.serialize_field("locked", &locked)?;
+        if locked {
+            state.serialize_field("children", &[] as &[UrlHiddenFile])?;
+        } else {
+            state.serialize_field("children", &ChildrenView(self))?;
+        }
         state.end()
     }
 }
 
-impl Serialize for UrlHiddenEntry {
+impl Serialize for ChildrenView<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        let parent = self.0;
+        serializer.collect_seq(parent.dir.children.iter().filter_map(|child| {
+            let name = match child {
+                UrlHiddenEntry::File(file) => &file.name,
+                UrlHiddenEntry::Dir(dir) => &dir.name,
+            };
+            let path = format!("{}/{}", parent.path, name);
+            if !(parent.can_list)(&path) {
+                return None;
+            }
+            Some(match child {
+                UrlHiddenEntry::File(file) => EntryView::File(FileView {
+                    file,
+                    downloads: (parent.downloads)(&path),
+                }),
+                UrlHiddenEntry::Dir(dir) => EntryView::Dir(DirView {
+                    dir,
+                    path,
+                    can_list: parent.can_list,
+                    unlocked: parent.unlocked,
+                    downloads: parent.downloads,
+                }),
+            })
+        }))
+    }
+}
+
+enum EntryView<'a> {
+    File(FileView<'a>),
+    Dir(DirView<'a>),
+}
+
+impl Serialize for EntryView<'_> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: Serializer,
     {
-        match *self {
-            UrlHiddenEntry::File(ref file) => file.serialize(serializer),
-            UrlHiddenEntry::Dir(ref dir) => dir.serialize(serializer),
+        match self {
+            EntryView::File(file) => file.serialize(serializer),
+            EntryView::Dir(dir) => dir.serialize(serializer),
         }
     }
 }
@@ -79,17 +184,26 @@ fn hide_url_for_file(file: &CombinableVfsFile) -> UrlHiddenFile {
     }
 }
 
-pub fn hide_url_for_dir(dir: &CombinableVfsDir) -> UrlHiddenDir {
-    let children = dir.list().into_iter().map(|entry| {
+/// `path` of the root is empty, and the others are like `/dir/sub_dir`.
+/// Directories where `is_protected(path)` is true are locked.
+pub fn hide_url_for_dir(dir: &CombinableVfsDir, path: &str, is_protected: &dyn Fn(&str) -> bool) -> UrlHiddenDir {
+    // unlisted entries can only be reached by their exact paths
+    let children = dir.list().into_iter().filter_map(|entry| {
         match entry {
-            VfsEntry::File(file) => UrlHiddenEntry::File(hide_url_for_file(&file)),
-            VfsEntry::Dir(dir) => UrlHiddenEntry::Dir(hide_url_for_dir(&dir)),
+            VfsEntry::File(file) if file.is_unlisted() => None,
+            VfsEntry::Dir(dir) if dir.is_unlisted() => None,
+            VfsEntry::File(file) => Some(UrlHiddenEntry::File(hide_url_for_file(&file))),
+            VfsEntry::Dir(dir) => {
+                let dir_path = format!("{}/{}", path, dir.name());
+                Some(UrlHiddenEntry::Dir(hide_url_for_dir(&dir, &dir_path, is_protected)))
+            }
         }
     }).collect();
     UrlHiddenDir {
         name: dir.name().to_string(),
         size: dir.size(),
         last_modified: dir.last_modified(),
+        locked: !path.is_empty() && is_protected(path),
         children,
     }
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
 pub mod path_compress;
 pub mod combine;
 pub mod hide_url;
+pub mod select;
+pub mod visibility;
+pub mod diff;
 
 pub trait VfsBasicMeta: Send + Sync {
     fn name(&self) -> &str;
@@ -8,9 +11,8 @@ pub trait VfsBasicMeta: Send + Sync {
     fn last_modified(&self) -> std::time::SystemTime;
 }
 
-pub trait VfsFile: VfsBasicMeta {
-    fn on_download(&self) -> String;
-}
+/// A file in the tree. It is downloaded through the mirrors of `CombinableVfsFile`, which resolve the urls on demand.
+pub trait VfsFile: VfsBasicMeta {}
 
 #[derive(Clone)]
 pub enum VfsEntry<File: VfsFile, Dir: VfsDir<File> + Clone> {
@@ -0,0 +1,110 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use rand::Rng;
+use crate::config_loader::config_struct::SelectStrategy;
+use crate::service::drive_health::DriveHealth;
+use crate::vfs::combine::DownloadLink;
+
+/// Who is downloading, and how the mirror should be selected for them.
+pub struct MirrorRequest<'a> {
+    pub strategy: SelectStrategy,
+    pub client_ip: &'a str,
+    pub health: &'a DriveHealth,
+}
+
+/// # Mirror Selector
+/// Selects one of the download links of a file. It keeps the state needed by the strategies,
+/// so it is shared by all the clones of a file.
+pub struct MirrorSelector {
+    links: Vec<DownloadLink>,
+    next: AtomicUsize,
+    last_used: Mutex<Vec<Option<Instant>>>,
+}
+
+impl MirrorSelector {
+    pub fn new(links: Vec<DownloadLink>) -> Self {
+        let last_used = Mutex::new(vec![None; links.len()]);
+        MirrorSelector {
+            links,
+            next: AtomicUsize::new(0),
+            last_used,
+        }
+    }
+
+    pub fn links(&self) -> &[DownloadLink] {
+        &self.links
+    }
+
+    /// Select a link by `request.strategy`.
+    /// Links on unhealthy drives are skipped, unless all the drives are unhealthy.
+    pub fn select(&self, request: &MirrorRequest) -> Option<&DownloadLink> {
+        let candidates = self.candidates(request.health);
+        if candidates.is_empty() {
+            return None;
+        }
+        let index = match request.strategy {
+            SelectStrategy::Random => {
+                candidates[rand::thread_rng().gen_range(0..candidates.len())]
+            }
+            SelectStrategy::Weighted => self.select_weighted(&candidates),
+            SelectStrategy::RoundRobin => {
+                candidates[self.next.fetch_add(1, Ordering::Relaxed) % candidates.len()]
+            }
+            SelectStrategy::LeastRecentlyUsed => self.select_least_recently_used(&candidates),
+            SelectStrategy::Sticky => {
+                let mut hasher = DefaultHasher::new();
+                request.client_ip.hash(&mut hasher);
+                candidates[(hasher.finish() % candidates.len() as u64) as usize]
+            }
+        };
+        Some(&self.links[index])
+    }
+
+    /// The links which can be offered, links on unhealthy drives are skipped unless all the drives are unhealthy.
+    pub fn available_links(&self, health: &DriveHealth) -> Vec<&DownloadLink> {
+        self.candidates(health).into_iter().map(|index| &self.links[index]).collect()
+    }
+
+    fn candidates(&self, health: &DriveHealth) -> Vec<usize> {
+        let healthy: Vec<usize> = (0..self.links.len())
+            .filter(|&index| health.is_healthy(&self.links[index].drive().name))
+            .collect();
+        if healthy.is_empty() {
+            (0..self.links.len()).collect()
+        } else {
+            healthy
+        }
+    }
+
+    /// The priority of the drive plus one, so that drives of priority 0 or less still get a share.
+    fn weight_of(&self, index: usize) -> u64 {
+        self.links[index].drive().priority.max(0) as u64 + 1
+    }
+
+    fn select_weighted(&self, candidates: &[usize]) -> usize {
+        let total: u64 = candidates.iter().map(|&index| self.weight_of(index)).sum();
+        if total == 0 {
+            return candidates[0];
+        }
+        let mut point = rand::thread_rng().gen_range(0..total);
+        for &index in candidates {
+            let weight = self.weight_of(index);
+            if point < weight {
+                return index;
+            }
+            point -= weight;
+        }
+        candidates[candidates.len() - 1]
+    }
+
+    fn select_least_recently_used(&self, candidates: &[usize]) -> usize {
+        let mut last_used = self.last_used.lock().unwrap();
+        // `None` (never used) is less than any `Some`
+        let index = *candidates.iter().min_by_key(|&&index| last_used[index]).unwrap();
+        last_used[index] = Some(Instant::now());
+        index
+    }
+}
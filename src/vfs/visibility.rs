@@ -0,0 +1,82 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    /// Listed in the file tree, and can be downloaded.
+    Listed,
+    /// Not listed in the file tree, but can still be downloaded by its exact path.
+    Unlisted,
+    /// Removed from the combined tree, as if it did not exist.
+    Hidden,
+}
+
+/// # Path Filter
+/// Decides the visibility of the entries by gitignore-style patterns, like `private/`, `*.tmp` or `/docs/**/draft`.
+/// - a pattern without `/` (except a trailing one) matches the name at any depth
+/// - a pattern with `/` is relative to the root of the drive
+/// - a pattern ending with `/` only matches directories
+/// - `!pattern` makes a previously matched entry visible again, the last matching pattern wins
+///
+/// An entry matched by both `hide` and `unlisted` is hidden.
+pub struct PathFilter {
+    hide: Gitignore,
+    unlisted: Gitignore,
+}
+
+impl PathFilter {
+    pub fn new(hide: &[String], unlisted: &[String]) -> Result<Self, String> {
+        Ok(PathFilter {
+            hide: build_patterns(hide)?,
+            unlisted: build_patterns(unlisted)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hide.is_empty() && self.unlisted.is_empty()
+    }
+
+    /// `path` is relative to the root, like `/dir/file`.
+    pub fn visibility(&self, path: &str, is_dir: bool) -> Visibility {
+        let path = path.trim_start_matches('/');
+        if let Match::Ignore(_) = self.hide.matched(path, is_dir) {
+            return Visibility::Hidden;
+        }
+        if let Match::Ignore(_) = self.unlisted.matched(path, is_dir) {
+            return Visibility::Unlisted;
+        }
+        Visibility::Listed
+    }
+}
+
+fn build_patterns(patterns: &[String]) -> Result<Gitignore, String> {
+    let mut builder = GitignoreBuilder::new("");
+    for pattern in patterns {
+        builder.add_line(None, pattern).map_err(|e| format!("Invalid pattern {}: {}", pattern, e))?;
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    #[test]
+    fn test_path_filter_visibility() {
+        let filter = PathFilter::new(
+            &patterns(&["private/", "*.tmp", "!keep.tmp"]),
+            &patterns(&["/share/secret.zip"]),
+        ).unwrap();
+        assert_eq!(filter.visibility("/private", true), Visibility::Hidden);
+        assert_eq!(filter.visibility("/docs/private", true), Visibility::Hidden);
+        assert_eq!(filter.visibility("/docs/private", false), Visibility::Listed);
+        assert_eq!(filter.visibility("/docs/a.tmp", false), Visibility::Hidden);
+        assert_eq!(filter.visibility("/docs/keep.tmp", false), Visibility::Listed);
+        assert_eq!(filter.visibility("/share/secret.zip", false), Visibility::Unlisted);
+        assert_eq!(filter.visibility("/other/share/secret.zip", false), Visibility::Listed);
+    }
+}